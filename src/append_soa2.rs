@@ -0,0 +1,325 @@
+use core::fmt::{Debug, Formatter, Result};
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::slice;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+use unadorned::{self, Extent, Unadorned};
+
+/// The number of rows in the first chunk. Each chunk after it is twice the size
+/// of the one before.
+const FIRST_CHUNK_BITS: usize = 5;
+const FIRST_CHUNK: usize = 1 << FIRST_CHUNK_BITS;
+
+struct Chunk<A, B> {
+    d0:    Unadorned<A>,
+    d1:    Unadorned<B>,
+    e:     Extent,
+    /// Set once the row at the same offset has been written.
+    ready: Box<[AtomicBool]>,
+}
+
+impl<A, B> Chunk<A, B> {
+    fn with_capacity(cap: usize) -> Chunk<A, B> {
+        unsafe {
+            let (d0, d0u) = Unadorned::with_capacity(cap);
+            let (d1, d1u) = Unadorned::with_capacity(cap);
+
+            let is_boring =
+                mem::size_of::<A>() == 0
+             && mem::size_of::<B>() == 0;
+
+            let e = unadorned::with_capacity_update(&[d0u, d1u], is_boring, cap);
+            let ready: Vec<_> = (0..cap).map(|_| AtomicBool::new(false)).collect();
+
+            Chunk { d0: d0, d1: d1, e: e, ready: ready.into_boxed_slice() }
+        }
+    }
+}
+
+/// Returns the capacity of the `k`th chunk.
+#[inline]
+fn chunk_capacity(k: usize) -> usize {
+    FIRST_CHUNK << k
+}
+
+/// Returns the chunk which holds the `index`th row, and the row's offset in it.
+#[inline]
+fn locate(index: usize) -> (usize, usize) {
+    let j = (index >> FIRST_CHUNK_BITS) + 1;
+    let k = (mem::size_of::<usize>() * 8 - 1) - j.leading_zeros() as usize;
+    (k, index - FIRST_CHUNK * ((1 << k) - 1))
+}
+
+/// An append-only struct-of-2-arrays type which many threads can push onto at
+/// once.
+///
+/// `push` takes `&self`, so an `AppendSoa2` can be shared between producer
+/// threads behind an `Arc` or a scoped borrow. Rows are stored in chunks which
+/// double in size, each allocated like a `Soa2`'s arrays and so aligned to
+/// 16-bytes. Chunks are never reallocated, so a row never moves once pushed.
+///
+/// Each push claims the next index with an atomic increment, writes its row,
+/// then flags the row as ready. Pushes never wait on each other. A row is
+/// committed once it and every row before it are ready, and `snapshot` only
+/// ever sees committed rows, so readers get a consistent prefix of the table,
+/// as one pair of slices per chunk. A producer that stalls mid-push only holds
+/// back what readers can see, not the other producers.
+pub struct AppendSoa2<A, B> {
+    chunks:    Box<[AtomicPtr<Chunk<A, B>>]>,
+    reserved:  AtomicUsize,
+    committed: AtomicUsize,
+    _marker:   PhantomData<(A, B)>,
+}
+
+unsafe impl<A: Send, B: Send> Send for AppendSoa2<A, B> {}
+unsafe impl<A: Send + Sync, B: Send + Sync> Sync for AppendSoa2<A, B> {}
+
+impl<A, B> AppendSoa2<A, B> {
+    /// Constructs a new, empty `AppendSoa2`.
+    ///
+    /// The SoA will not allocate any chunks until elements are pushed onto it.
+    pub fn new() -> AppendSoa2<A, B> {
+        let num_chunks = mem::size_of::<usize>() * 8 - FIRST_CHUNK_BITS;
+        let chunks: Vec<_> = (0..num_chunks).map(|_| AtomicPtr::new(ptr::null_mut())).collect();
+
+        AppendSoa2 {
+            chunks:    chunks.into_boxed_slice(),
+            reserved:  AtomicUsize::new(0),
+            committed: AtomicUsize::new(0),
+            _marker:   PhantomData,
+        }
+    }
+
+    /// Returns `true` if the `index`th row has been written.
+    #[inline]
+    fn is_ready(&self, index: usize) -> bool {
+        let (k, offset) = locate(index);
+        let c = self.chunks[k].load(Ordering::Acquire);
+        !c.is_null() && unsafe { (*c).ready[offset].load(Ordering::Acquire) }
+    }
+
+    /// Returns the number of committed elements in the SoA.
+    ///
+    /// This is the length of the longest prefix of rows which are all ready.
+    pub fn len(&self) -> usize {
+        let committed = self.committed.load(Ordering::Acquire);
+
+        let mut len = committed;
+        while len != !0 && self.is_ready(len) {
+            len += 1;
+        }
+
+        if len != committed {
+            self.committed.fetch_max(len, Ordering::AcqRel);
+        }
+
+        len
+    }
+
+    /// Returns `true` if the SoA contains no committed elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the `k`th chunk, allocating it if no other thread has yet.
+    fn chunk(&self, k: usize) -> *mut Chunk<A, B> {
+        let slot = &self.chunks[k];
+
+        let existing = slot.load(Ordering::Acquire);
+        if !existing.is_null() {
+            return existing;
+        }
+
+        let fresh = Box::into_raw(Box::new(Chunk::with_capacity(chunk_capacity(k))));
+        match slot.compare_exchange(ptr::null_mut(), fresh, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_)     => fresh,
+            Err(prev) => {
+                // Another thread got there first. Ours has no rows, so only
+                // its buffers need freeing.
+                unsafe {
+                    let c = Box::from_raw(fresh);
+                    c.d0.drop(&c.e);
+                    c.d1.drop(&c.e);
+                }
+                prev
+            },
+        }
+    }
+
+    /// Appends an element to the back of the SoA, and returns its index.
+    ///
+    /// The element becomes visible to `snapshot` once every element pushed
+    /// before it has been written too. This never waits for other pushes.
+    ///
+    /// Panics if the number of elements in the SoA overflows a `usize`.
+    pub fn push(&self, value: (A, B)) -> usize {
+        let index = self.reserved.fetch_add(1, Ordering::Relaxed);
+        assert!(index != !0, "length overflow");
+
+        let (k, offset) = locate(index);
+        unsafe {
+            let c = self.chunk(k);
+            ptr::write((*c).d0.as_ptr().offset(offset as isize) as *mut A, value.0);
+            ptr::write((*c).d1.as_ptr().offset(offset as isize) as *mut B, value.1);
+            (*c).ready[offset].store(true, Ordering::Release);
+        }
+
+        index
+    }
+
+    /// Returns a view of the elements which have been committed so far.
+    ///
+    /// Elements pushed after the snapshot is taken are not part of it.
+    #[inline]
+    pub fn snapshot(&self) -> Snapshot<A, B> {
+        Snapshot { soa: self, len: self.len() }
+    }
+
+    /// Returns the `index`th element, or `None` if it hasn't been committed.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(&A, &B)> {
+        self.snapshot().get(index)
+    }
+
+    /// Returns mutable slices over the SoA's chunks.
+    ///
+    /// This needs exclusive access, so no pushes can be in flight.
+    pub fn chunks_mut(&mut self) -> Vec<(&mut [A], &mut [B])> {
+        let len = self.len();
+        let mut ret = Vec::new();
+
+        let mut k = 0;
+        let mut start = 0;
+        while start < len {
+            let n = chunk_capacity(k).min(len - start);
+            unsafe {
+                let c = self.chunks[k].load(Ordering::Relaxed);
+                ret.push(((*c).d0.as_mut_slice(n), (*c).d1.as_mut_slice(n)));
+            }
+            start += n;
+            k += 1;
+        }
+
+        ret
+    }
+}
+
+impl<A, B> Default for AppendSoa2<A, B> {
+    fn default() -> AppendSoa2<A, B> { AppendSoa2::new() }
+}
+
+impl<A: Debug, B: Debug> Debug for AppendSoa2<A, B> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        Debug::fmt(&self.snapshot(), f)
+    }
+}
+
+impl<A, B> Drop for AppendSoa2<A, B> {
+    fn drop(&mut self) {
+        // A push that panicked can leave a hole, so every chunk is visited and
+        // only the rows which were written are dropped.
+        for slot in self.chunks.iter() {
+            let c = slot.load(Ordering::Relaxed);
+            if c.is_null() {
+                continue;
+            }
+
+            unsafe {
+                let c = Box::from_raw(c);
+                for (i, ready) in c.ready.iter().enumerate() {
+                    if ready.load(Ordering::Relaxed) {
+                        ptr::drop_in_place(c.d0.as_ptr().offset(i as isize) as *mut A);
+                        ptr::drop_in_place(c.d1.as_ptr().offset(i as isize) as *mut B);
+                    }
+                }
+
+                // The rows are gone, so only the buffers are left to free.
+                c.d0.drop(&c.e);
+                c.d1.drop(&c.e);
+            }
+        }
+    }
+}
+
+/// A consistent view of the committed prefix of an `AppendSoa2`.
+pub struct Snapshot<'a, A: 'a, B: 'a> {
+    soa: &'a AppendSoa2<A, B>,
+    len: usize,
+}
+
+impl<'a, A, B> Snapshot<'a, A, B> {
+    /// Returns the number of elements in the snapshot.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the snapshot contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the `index`th element, or `None` if it is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(&'a A, &'a B)> {
+        if index >= self.len {
+            return None;
+        }
+
+        let (k, offset) = locate(index);
+        unsafe {
+            let c = self.soa.chunks[k].load(Ordering::Acquire);
+            Some((&*(*c).d0.as_ptr().offset(offset as isize),
+                  &*(*c).d1.as_ptr().offset(offset as isize)))
+        }
+    }
+
+    /// Returns an iterator over the snapshot's chunks, each as a pair of
+    /// slices over its arrays.
+    #[inline]
+    pub fn chunks(&self) -> Chunks<'a, A, B> {
+        Chunks { soa: self.soa, k: 0, remaining: self.len }
+    }
+}
+
+impl<'a, A: Debug, B: Debug> Debug for Snapshot<'a, A, B> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let mut l = f.debug_list();
+        for (d0, d1) in self.chunks() {
+            l.entries(d0.iter().zip(d1.iter()));
+        }
+        l.finish()
+    }
+}
+
+/// An iterator over the chunks of a `Snapshot`.
+pub struct Chunks<'a, A: 'a, B: 'a> {
+    soa:       &'a AppendSoa2<A, B>,
+    k:         usize,
+    remaining: usize,
+}
+
+impl<'a, A, B> Iterator for Chunks<'a, A, B> {
+    type Item = (&'a [A], &'a [B]);
+
+    fn next(&mut self) -> Option<(&'a [A], &'a [B])> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let n = chunk_capacity(self.k).min(self.remaining);
+        let ret = unsafe {
+            let c = self.soa.chunks[self.k].load(Ordering::Acquire);
+            (slice::from_raw_parts((*c).d0.as_ptr(), n),
+             slice::from_raw_parts((*c).d1.as_ptr(), n))
+        };
+
+        self.k += 1;
+        self.remaining -= n;
+        Some(ret)
+    }
+}
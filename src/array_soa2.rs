@@ -0,0 +1,353 @@
+use core::cmp::Ordering;
+use core::default::Default;
+use core::fmt::{self, Debug, Formatter};
+use core::iter;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::slice;
+
+/// The error returned by `try_push` and `try_insert` when the SoA is full.
+///
+/// It hands back the row that didn't fit.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CapacityError<T>(pub T);
+
+impl<T> CapacityError<T> {
+    /// Returns the row that could not be added.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Display for CapacityError<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("insufficient capacity")
+    }
+}
+
+/// Keeps each inline array on a 16-byte boundary, like the heap allocated
+/// ones.
+#[repr(C, align(16))]
+struct Aligned<T>(T);
+
+/// A fixed-capacity struct-of-2-arrays type, with inline contents.
+///
+/// This is the `arrayvec` to `Soa2`'s `Vec`: each array lives inside the
+/// structure itself, with room for exactly `N` rows, and nothing is ever heap
+/// allocated. Pushing onto a full `ArraySoa2` panics, and `try_push` hands the
+/// row back instead.
+///
+/// Both arrays are aligned to 16-bytes, so the same SIMD kernels that run over
+/// a `Soa2`'s slices work here too.
+pub struct ArraySoa2<A, B, const N: usize> {
+    d0:  Aligned<[MaybeUninit<A>; N]>,
+    d1:  Aligned<[MaybeUninit<B>; N]>,
+    len: usize,
+}
+
+impl<A, B, const N: usize> ArraySoa2<A, B, N> {
+    /// Constructs a new, empty `ArraySoa2`.
+    #[inline]
+    pub fn new() -> ArraySoa2<A, B, N> {
+        unsafe {
+            // An array of `MaybeUninit`s doesn't need initializing.
+            ArraySoa2 {
+                d0:  Aligned(MaybeUninit::uninit().assume_init()),
+                d1:  Aligned(MaybeUninit::uninit().assume_init()),
+                len: 0,
+            }
+        }
+    }
+
+    /// Returns the number of tuples stored in the SoA.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the SoA contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if the SoA has no room for any more elements.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Returns the number of elements the SoA can hold: always `N`.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Sets the length of the SoA.
+    ///
+    /// This will explicitly set the size of the soa, without actually
+    /// modifying its buffers, so it is up to the caller to ensure that the
+    /// SoA is actually the specified size.
+    #[inline]
+    pub unsafe fn set_len(&mut self, len: usize) {
+        debug_assert!(len <= N);
+        self.len = len;
+    }
+
+    /// Returns a pair of pointers to the start of the data in an SoA.
+    #[inline]
+    pub fn as_ptrs(&self) -> (*const A, *const B) {
+        (self.d0.0.as_ptr() as *const A, self.d1.0.as_ptr() as *const B)
+    }
+
+    /// Returns a pair of pointers to the start of the mutable data in an SoA.
+    #[inline]
+    pub fn as_mut_ptrs(&mut self) -> (*mut A, *mut B) {
+        (self.d0.0.as_mut_ptr() as *mut A, self.d1.0.as_mut_ptr() as *mut B)
+    }
+
+    /// Returns slices over the SoA's elements.
+    #[inline]
+    pub fn as_slices<'a>(&'a self) -> (&'a [A], &'a [B]) {
+        unsafe {
+            let (p0, p1) = self.as_ptrs();
+            (slice::from_raw_parts(p0, self.len), slice::from_raw_parts(p1, self.len))
+        }
+    }
+
+    /// Returns mutable slices over the SoA's elements.
+    #[inline]
+    pub fn as_mut_slices<'a>(&'a mut self) -> (&'a mut [A], &'a mut [B]) {
+        unsafe {
+            let len = self.len;
+            let (p0, p1) = self.as_mut_ptrs();
+            (slice::from_raw_parts_mut(p0, len), slice::from_raw_parts_mut(p1, len))
+        }
+    }
+
+    /// Returns iterators over the SoA's elements.
+    #[inline]
+    pub fn iters(&self) -> (slice::Iter<A>, slice::Iter<B>) {
+        let (d0, d1) = self.as_slices();
+        (d0.iter(), d1.iter())
+    }
+
+    /// Returns a single iterator over the SoA's elements, zipped up.
+    #[inline]
+    pub fn zip_iter(&self) -> iter::Zip<slice::Iter<A>, slice::Iter<B>> {
+        let (d0, d1) = self.iters();
+        d0.zip(d1)
+    }
+
+    /// Returns mutable iterators over the SoA's elements.
+    #[inline]
+    pub fn iters_mut(&mut self) -> (slice::IterMut<A>, slice::IterMut<B>) {
+        let (d0, d1) = self.as_mut_slices();
+        (d0.iter_mut(), d1.iter_mut())
+    }
+
+    /// Returns a single iterator over the SoA's elements, zipped up.
+    #[inline]
+    pub fn zip_iter_mut(&mut self) -> iter::Zip<slice::IterMut<A>, slice::IterMut<B>> {
+        let (d0, d1) = self.iters_mut();
+        d0.zip(d1)
+    }
+
+    /// Appends an element to the back of the SoA.
+    ///
+    /// Panics if the SoA is full.
+    #[inline]
+    pub fn push(&mut self, value: (A, B)) {
+        if let Err(_) = self.try_push(value) {
+            panic!("`push` called on a full ArraySoa2 (capacity {})", N);
+        }
+    }
+
+    /// Appends an element to the back of the SoA, or returns it inside an
+    /// error if the SoA is full.
+    #[inline]
+    pub fn try_push(&mut self, value: (A, B)) -> Result<(), CapacityError<(A, B)>> {
+        if self.len == N { return Err(CapacityError(value)) }
+
+        unsafe {
+            let len = self.len;
+            let (p0, p1) = self.as_mut_ptrs();
+            ptr::write(p0.offset(len as isize), value.0);
+            ptr::write(p1.offset(len as isize), value.1);
+        }
+
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes the last element from the SoA and returns it, or `None` if
+    /// empty.
+    #[inline]
+    pub fn pop(&mut self) -> Option<(A, B)> {
+        if self.len == 0 { return None }
+
+        unsafe {
+            self.len -= 1;
+            let len = self.len;
+            let (p0, p1) = self.as_ptrs();
+            Some((ptr::read(p0.offset(len as isize)), ptr::read(p1.offset(len as isize))))
+        }
+    }
+
+    /// Shorten the SoA, dropping excess elements.
+    ///
+    /// If `len` is greater than the soa's current length, this has no effect.
+    pub fn truncate(&mut self, len: usize) {
+        while self.len > len {
+            self.pop();
+        }
+    }
+
+    /// Clears the SoA, removing all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Removes an element from anywhere in the SoA and returns it, replacing it
+    /// with the last element.
+    ///
+    /// This does not preserve ordering, but is O(1).
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn swap_remove(&mut self, index: usize) -> (A, B) {
+        let length = self.len;
+        {
+            let (d0, d1) = self.as_mut_slices();
+            d0.swap(index, length - 1);
+            d1.swap(index, length - 1);
+        }
+        self.pop().unwrap()
+    }
+
+    /// Inserts an element at position `index` within the SoA, shifting all
+    /// elements after position `index` one position to the right.
+    ///
+    /// Panics if `index` is greater than the SoA's length, or if the SoA is
+    /// full.
+    pub fn insert(&mut self, index: usize, element: (A, B)) {
+        if let Err(_) = self.try_insert(index, element) {
+            panic!("`insert` called on a full ArraySoa2 (capacity {})", N);
+        }
+    }
+
+    /// Inserts an element at position `index` within the SoA, or returns it
+    /// inside an error if the SoA is full.
+    ///
+    /// Panics if `index` is greater than the SoA's length.
+    pub fn try_insert(&mut self, index: usize, element: (A, B)) -> Result<(), CapacityError<(A, B)>> {
+        assert!(index <= self.len);
+        if self.len == N { return Err(CapacityError(element)) }
+
+        unsafe {
+            let len = self.len;
+            let (p0, p1) = self.as_mut_ptrs();
+            let (p0, p1) = (p0.offset(index as isize), p1.offset(index as isize));
+            ptr::copy(p0, p0.offset(1), len - index);
+            ptr::copy(p1, p1.offset(1), len - index);
+            ptr::write(p0, element.0);
+            ptr::write(p1, element.1);
+        }
+
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes and returns the elements at position `index` within the SoA,
+    /// shifting all elements after position `index` one position to the left.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> (A, B) {
+        assert!(index < self.len);
+
+        unsafe {
+            let len = self.len;
+            let (p0, p1) = self.as_mut_ptrs();
+            let (p0, p1) = (p0.offset(index as isize), p1.offset(index as isize));
+            let ret = (ptr::read(p0), ptr::read(p1));
+            ptr::copy(p0.offset(1), p0, len - index - 1);
+            ptr::copy(p1.offset(1), p1, len - index - 1);
+            self.len -= 1;
+            ret
+        }
+    }
+
+    /// Returns only the element specified by the predicate.
+    ///
+    /// In other words, remove all elements `e` such that `f(&e)` returns false.
+    /// This method operates in place and preserves the order of the retained
+    /// elements.
+    pub fn retain<F>(&mut self, mut f: F) where F: FnMut((&A, &B)) -> bool {
+        let len = self.len();
+        let mut del = 0;
+
+        {
+            let (d0, d1) = self.as_mut_slices();
+
+            for i in 0..len {
+                if !f((&d0[i], &d1[i])) {
+                    del += 1;
+                } else if del > 0 {
+                    d0.swap(i-del, i);
+                    d1.swap(i-del, i);
+                }
+            }
+        }
+
+        self.truncate(len - del);
+    }
+}
+
+impl<A: Clone, B: Clone, const N: usize> Clone for ArraySoa2<A, B, N> {
+    fn clone(&self) -> ArraySoa2<A, B, N> {
+        let mut ret = ArraySoa2::new();
+        for (a, b) in self.zip_iter() {
+            ret.push((a.clone(), b.clone()));
+        }
+        ret
+    }
+}
+
+impl<A0, B0, A1, B1, const N: usize, const M: usize> PartialEq<ArraySoa2<A1, B1, M>> for ArraySoa2<A0, B0, N>
+  where A0: PartialEq<A1>, B0: PartialEq<B1> {
+    #[inline]
+    fn eq(&self, other: &ArraySoa2<A1, B1, M>) -> bool {
+        let (a0, b0) = self.as_slices();
+        let (a1, b1) = other.as_slices();
+
+        PartialEq::eq(a0, a1) && PartialEq::eq(b0, b1)
+    }
+}
+
+impl<A: Eq, B: Eq, const N: usize> Eq for ArraySoa2<A, B, N> {}
+
+impl<A: PartialOrd, B: PartialOrd, const N: usize> PartialOrd for ArraySoa2<A, B, N> {
+    #[inline]
+    fn partial_cmp(&self, other: &ArraySoa2<A, B, N>) -> Option<Ordering> {
+        self.zip_iter().partial_cmp(other.zip_iter())
+    }
+}
+
+impl<A, B, const N: usize> Default for ArraySoa2<A, B, N> {
+    fn default() -> ArraySoa2<A, B, N> { ArraySoa2::new() }
+}
+
+impl<A: Debug, B: Debug, const N: usize> Debug for ArraySoa2<A, B, N> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Debug::fmt(&self.as_slices(), f)
+    }
+}
+
+impl<A, B, const N: usize> Drop for ArraySoa2<A, B, N> {
+    #[inline]
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
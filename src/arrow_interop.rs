@@ -0,0 +1,201 @@
+//! Conversions between SoAs of primitive types and Apache Arrow arrays.
+//!
+//! Going to Arrow never copies: each array of the SoA becomes the values
+//! buffer of a `PrimitiveArray`. Coming back from Arrow always copies into a
+//! fresh SoA. An Arrow buffer can't tell us how it was allocated, only where
+//! it starts, and an SoA must free its arrays with 16-byte alignment, so
+//! adopting one would risk freeing it with the wrong layout.
+
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, PrimitiveArray, RecordBatch};
+use arrow::buffer::ScalarBuffer;
+use arrow::datatypes::*;
+use arrow::error::ArrowError;
+
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+
+/// Primitive types with an Arrow counterpart, which may be used as the
+/// elements of an SoA that is converted to and from Arrow.
+pub trait ArrowColumn: ArrowNativeType {
+    /// The Arrow type whose native representation is `Self`.
+    type Arrow: ArrowPrimitiveType<Native=Self>;
+}
+
+macro_rules! arrow_column {
+    ($($native:ty => $arrow:ty),*) => {
+        $(impl ArrowColumn for $native { type Arrow = $arrow; })*
+    }
+}
+
+arrow_column! {
+    i8  => Int8Type,  i16 => Int16Type,  i32 => Int32Type,  i64 => Int64Type,
+    u8  => UInt8Type, u16 => UInt16Type, u32 => UInt32Type, u64 => UInt64Type,
+    f32 => Float32Type, f64 => Float64Type
+}
+
+fn to_array<T: ArrowColumn>(v: Vec<T>) -> PrimitiveArray<T::Arrow> {
+    PrimitiveArray::new(ScalarBuffer::from(v), None)
+}
+
+fn take_values<T: ArrowColumn>(a: PrimitiveArray<T::Arrow>) -> Result<ScalarBuffer<T>, ArrowError> {
+    if a.null_count() != 0 {
+        return Err(ArrowError::InvalidArgumentError(
+            "SoA arrays can't hold nulls".to_string()));
+    }
+
+    let (_, values, _) = a.into_parts();
+    Ok(values)
+}
+
+fn downcast<T: ArrowColumn>(column: ArrayRef) -> Result<PrimitiveArray<T::Arrow>, ArrowError> {
+    match column.as_primitive_opt::<T::Arrow>() {
+        Some(a) => Ok(a.clone()),
+        None => Err(ArrowError::InvalidArgumentError(format!(
+            "expected a column of {}, found {}", T::Arrow::DATA_TYPE, column.data_type()))),
+    }
+}
+
+fn check_lengths(lens: &[usize]) -> Result<(), ArrowError> {
+    if lens.iter().any(|&len| len != lens[0]) {
+        return Err(ArrowError::InvalidArgumentError(
+            format!("SoA arrays have unequal lengths: {:?}", lens)));
+    }
+
+    Ok(())
+}
+
+fn take_columns(batch: RecordBatch, expected: usize) -> Result<Vec<ArrayRef>, ArrowError> {
+    if batch.num_columns() != expected {
+        return Err(ArrowError::SchemaError(format!(
+            "expected {} columns, found {}", expected, batch.num_columns())));
+    }
+
+    Ok(batch.columns().to_vec())
+}
+
+fn field<T: ArrowColumn>(name: &str) -> Field {
+    Field::new(name, T::Arrow::DATA_TYPE, false)
+}
+
+impl<A: ArrowColumn, B: ArrowColumn> Soa2<A, B> {
+    /// Converts the SoA into one Arrow array per column, without copying.
+    pub fn into_arrays(self) -> (PrimitiveArray<A::Arrow>, PrimitiveArray<B::Arrow>) {
+        let (v0, v1) = self.into_vecs();
+        (to_array(v0), to_array(v1))
+    }
+
+    /// Builds an SoA from one Arrow array per column.
+    ///
+    /// The arrays' values are copied into the SoA. Returns an error if the arrays contain nulls or have unequal lengths.
+    pub fn from_arrays(a0: PrimitiveArray<A::Arrow>, a1: PrimitiveArray<B::Arrow>) -> Result<Soa2<A, B>, ArrowError> {
+        try!(check_lengths(&[a0.len(), a1.len()]));
+
+        let v0 = try!(take_values(a0));
+        let v1 = try!(take_values(a1));
+
+        unsafe { Ok(Soa2::from_raw_bufs(v0.as_ptr(), v1.as_ptr(), v0.len())) }
+    }
+
+    /// Converts the SoA into a `RecordBatch` with the given column names,
+    /// without copying.
+    pub fn into_record_batch(self, names: [&str; 2]) -> Result<RecordBatch, ArrowError> {
+        let schema = Schema::new(vec![field::<A>(names[0]), field::<B>(names[1])]);
+        let (a0, a1) = self.into_arrays();
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(a0), Arc::new(a1)])
+    }
+
+    /// Builds an SoA from a two column `RecordBatch`.
+    ///
+    /// The columns' values are copied into the SoA.
+    pub fn from_record_batch(batch: RecordBatch) -> Result<Soa2<A, B>, ArrowError> {
+        let mut columns = try!(take_columns(batch, 2)).into_iter();
+        let a0 = try!(downcast::<A>(columns.next().unwrap()));
+        let a1 = try!(downcast::<B>(columns.next().unwrap()));
+        Soa2::from_arrays(a0, a1)
+    }
+}
+
+impl<A: ArrowColumn, B: ArrowColumn, C: ArrowColumn> Soa3<A, B, C> {
+    /// Converts the SoA into one Arrow array per column, without copying.
+    pub fn into_arrays(self) -> (PrimitiveArray<A::Arrow>, PrimitiveArray<B::Arrow>, PrimitiveArray<C::Arrow>) {
+        let (v0, v1, v2) = self.into_vecs();
+        (to_array(v0), to_array(v1), to_array(v2))
+    }
+
+    /// Builds an SoA from one Arrow array per column.
+    ///
+    /// The arrays' values are copied into the SoA. Returns an error if the arrays contain nulls or have unequal lengths.
+    pub fn from_arrays(a0: PrimitiveArray<A::Arrow>, a1: PrimitiveArray<B::Arrow>, a2: PrimitiveArray<C::Arrow>) -> Result<Soa3<A, B, C>, ArrowError> {
+        try!(check_lengths(&[a0.len(), a1.len(), a2.len()]));
+
+        let v0 = try!(take_values(a0));
+        let v1 = try!(take_values(a1));
+        let v2 = try!(take_values(a2));
+
+        unsafe { Ok(Soa3::from_raw_bufs(v0.as_ptr(), v1.as_ptr(), v2.as_ptr(), v0.len())) }
+    }
+
+    /// Converts the SoA into a `RecordBatch` with the given column names,
+    /// without copying.
+    pub fn into_record_batch(self, names: [&str; 3]) -> Result<RecordBatch, ArrowError> {
+        let schema = Schema::new(vec![field::<A>(names[0]), field::<B>(names[1]), field::<C>(names[2])]);
+        let (a0, a1, a2) = self.into_arrays();
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(a0), Arc::new(a1), Arc::new(a2)])
+    }
+
+    /// Builds an SoA from a three column `RecordBatch`.
+    ///
+    /// The columns' values are copied into the SoA.
+    pub fn from_record_batch(batch: RecordBatch) -> Result<Soa3<A, B, C>, ArrowError> {
+        let mut columns = try!(take_columns(batch, 3)).into_iter();
+        let a0 = try!(downcast::<A>(columns.next().unwrap()));
+        let a1 = try!(downcast::<B>(columns.next().unwrap()));
+        let a2 = try!(downcast::<C>(columns.next().unwrap()));
+        Soa3::from_arrays(a0, a1, a2)
+    }
+}
+
+impl<A: ArrowColumn, B: ArrowColumn, C: ArrowColumn, D: ArrowColumn> Soa4<A, B, C, D> {
+    /// Converts the SoA into one Arrow array per column, without copying.
+    pub fn into_arrays(self) -> (PrimitiveArray<A::Arrow>, PrimitiveArray<B::Arrow>, PrimitiveArray<C::Arrow>, PrimitiveArray<D::Arrow>) {
+        let (v0, v1, v2, v3) = self.into_vecs();
+        (to_array(v0), to_array(v1), to_array(v2), to_array(v3))
+    }
+
+    /// Builds an SoA from one Arrow array per column.
+    ///
+    /// The arrays' values are copied into the SoA. Returns an error if the arrays contain nulls or have unequal lengths.
+    pub fn from_arrays(a0: PrimitiveArray<A::Arrow>, a1: PrimitiveArray<B::Arrow>, a2: PrimitiveArray<C::Arrow>, a3: PrimitiveArray<D::Arrow>) -> Result<Soa4<A, B, C, D>, ArrowError> {
+        try!(check_lengths(&[a0.len(), a1.len(), a2.len(), a3.len()]));
+
+        let v0 = try!(take_values(a0));
+        let v1 = try!(take_values(a1));
+        let v2 = try!(take_values(a2));
+        let v3 = try!(take_values(a3));
+
+        unsafe { Ok(Soa4::from_raw_bufs(v0.as_ptr(), v1.as_ptr(), v2.as_ptr(), v3.as_ptr(), v0.len())) }
+    }
+
+    /// Converts the SoA into a `RecordBatch` with the given column names,
+    /// without copying.
+    pub fn into_record_batch(self, names: [&str; 4]) -> Result<RecordBatch, ArrowError> {
+        let schema = Schema::new(vec![field::<A>(names[0]), field::<B>(names[1]), field::<C>(names[2]), field::<D>(names[3])]);
+        let (a0, a1, a2, a3) = self.into_arrays();
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(a0), Arc::new(a1), Arc::new(a2), Arc::new(a3)])
+    }
+
+    /// Builds an SoA from a four column `RecordBatch`.
+    ///
+    /// The columns' values are copied into the SoA.
+    pub fn from_record_batch(batch: RecordBatch) -> Result<Soa4<A, B, C, D>, ArrowError> {
+        let mut columns = try!(take_columns(batch, 4)).into_iter();
+        let a0 = try!(downcast::<A>(columns.next().unwrap()));
+        let a1 = try!(downcast::<B>(columns.next().unwrap()));
+        let a2 = try!(downcast::<C>(columns.next().unwrap()));
+        let a3 = try!(downcast::<D>(columns.next().unwrap()));
+        Soa4::from_arrays(a0, a1, a2, a3)
+    }
+}
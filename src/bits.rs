@@ -0,0 +1,245 @@
+//! An SoA with an extra array of `bool`s, packed one bit per row.
+//!
+//! A `Soa3<f32, f32, bool>` spends a byte per row on its flag, and the flags
+//! can only be combined a byte at a time. `BitSoa<Soa2<f32, f32>>` keeps the
+//! same rows, but stores the flags in a `Bitmask`, so they take an eighth of
+//! the space and can be combined with other masks 64 rows at a time.
+//!
+//! `NullableSoa` is built on a `BitSoa`, with the bits as its validity bitmap.
+//!
+//! # Limitations
+//!
+//! There is no `Bits` element type which can sit in a `Soa3<f32, f32, Bits>`
+//! itself. Every array of a `SoaN` is an `Unadorned<T>` of whole elements,
+//! handed out as `&[T]` by `as_slices`, `column` and friends, and a bit-packed
+//! array has no such slice to hand out. So the bits live beside the SoA
+//! rather than in it: a `BitSoa` has exactly one bit array, always its last,
+//! and the rows of `S` are reached through `soa()` and `column::<N>()` rather
+//! than as wider tuples. `S`'s own methods, called through `soa()`, don't see
+//! the bits; `BitSoa` wraps the ones which add or remove rows to keep the two
+//! in step.
+
+use core::fmt::{Debug, Formatter, Result};
+
+use column::{Column, ColumnMut};
+use mask::Bitmask;
+use table::Table;
+
+/// An SoA with an extra bit-packed array of `bool`s.
+///
+/// Each row is a row of `S` plus a bit. Rows are added and removed as they
+/// would be in `S`, and the bits move with them.
+pub struct BitSoa<S: Table> {
+    soa:  S,
+    bits: Bitmask,
+}
+
+impl<S: Table> BitSoa<S> {
+    /// Constructs a new, empty SoA.
+    pub fn new() -> BitSoa<S> {
+        BitSoa { soa: S::new(), bits: Bitmask::new(0) }
+    }
+
+    /// Pairs each row of `soa` with a cleared bit.
+    pub fn from_soa(soa: S) -> BitSoa<S> {
+        let bits = Bitmask::new(soa.len());
+        BitSoa { soa: soa, bits: bits }
+    }
+
+    /// Pairs each row of `soa` with the same bit of `bits`.
+    ///
+    /// Panics if `bits` isn't the same length as `soa`.
+    pub fn from_parts(soa: S, bits: Bitmask) -> BitSoa<S> {
+        assert_eq!(soa.len(), bits.len());
+        BitSoa { soa: soa, bits: bits }
+    }
+
+    /// Returns the number of rows in the SoA.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.soa.len()
+    }
+
+    /// Returns `true` if the SoA contains no rows.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.soa.len() == 0
+    }
+
+    /// Appends a row.
+    #[inline]
+    pub fn push(&mut self, row: S::Row, bit: bool) {
+        self.soa.push(row);
+        self.bits.push(bit);
+    }
+
+    /// Removes the last row and returns it, or `None` if the SoA is empty.
+    #[inline]
+    pub fn pop(&mut self) -> Option<(S::Row, bool)> {
+        self.soa.pop().map(|row| (row, self.bits.pop().unwrap()))
+    }
+
+    /// Removes the `index`th row and returns it, replacing it with the last
+    /// row.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> (S::Row, bool) {
+        let bit = self.bits.swap_remove(index);
+        (self.soa.swap_remove(index), bit)
+    }
+
+    /// Keeps only the rows for which `f` returns `true`, preserving their
+    /// order.
+    ///
+    /// Every row is passed to `f` before any is removed, so if `f` panics the
+    /// SoA is left untouched.
+    pub fn retain<F>(&mut self, mut f: F) where F: for<'a> FnMut(S::Ref<'a>, bool) -> bool {
+        let mut keep = Bitmask::new(self.len());
+        for i in 0..self.len() {
+            let row = self.soa.get(i).unwrap();
+            keep.set(i, f(row, self.bits.get(i)));
+        }
+
+        let mut i = 0;
+        self.soa.retain(|_| {
+            let k = keep.get(i);
+            i += 1;
+            k
+        });
+
+        let mut bits = Bitmask::new(0);
+        for i in keep.iter_ones() {
+            bits.push(self.bits.get(i));
+        }
+        self.bits = bits;
+    }
+
+    /// Shortens the SoA, keeping the first `len` rows.
+    pub fn truncate(&mut self, len: usize) {
+        self.soa.truncate(len);
+        self.bits.truncate(len);
+    }
+
+    /// Removes every row.
+    pub fn clear(&mut self) {
+        self.soa.clear();
+        self.bits.truncate(0);
+    }
+
+    /// Returns the `index`th row, or `None` if it is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(S::Ref<'_>, bool)> {
+        self.soa.get(index).map(|row| (row, self.bits.get(index)))
+    }
+
+    /// Returns the `index`th bit.
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn bit(&self, index: usize) -> bool {
+        self.bits.get(index)
+    }
+
+    /// Sets the `index`th bit.
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn set_bit(&mut self, index: usize, bit: bool) {
+        self.bits.set(index, bit)
+    }
+
+    /// Returns the bits. Bit `i` belongs to row `i`.
+    #[inline]
+    pub fn bits(&self) -> &Bitmask {
+        &self.bits
+    }
+
+    /// Returns an iterator over the words the bits are packed into. Row `i`'s
+    /// bit is bit `i % 64` of word `i / 64`, and any bits past the last row are
+    /// zero.
+    #[inline]
+    pub fn words(&self) -> ::core::iter::Cloned<::core::slice::Iter<u64>> {
+        self.bits.as_words().iter().cloned()
+    }
+
+    /// Clears every bit not set in `mask`.
+    ///
+    /// Panics if `mask` isn't the same length as the SoA.
+    #[inline]
+    pub fn and_bits(&mut self, mask: &Bitmask) {
+        self.bits &= mask;
+    }
+
+    /// Sets every bit set in `mask`.
+    ///
+    /// Panics if `mask` isn't the same length as the SoA.
+    #[inline]
+    pub fn or_bits(&mut self, mask: &Bitmask) {
+        self.bits |= mask;
+    }
+
+    /// Flips every bit set in `mask`.
+    ///
+    /// Panics if `mask` isn't the same length as the SoA.
+    #[inline]
+    pub fn xor_bits(&mut self, mask: &Bitmask) {
+        self.bits ^= mask;
+    }
+
+    /// Flips every bit.
+    #[inline]
+    pub fn not_bits(&mut self) {
+        self.bits.negate();
+    }
+
+    /// Returns the SoA holding the other arrays.
+    #[inline]
+    pub fn soa(&self) -> &S {
+        &self.soa
+    }
+
+    /// Returns a slice over the `N`th of the other arrays.
+    #[inline]
+    pub fn column<const N: usize>(&self) -> &[<S as Column<N>>::Elem]
+      where S: Column<N> {
+        Column::<N>::column(&self.soa)
+    }
+
+    /// Returns a mutable slice over the `N`th of the other arrays.
+    #[inline]
+    pub fn column_mut<const N: usize>(&mut self) -> &mut [<S as Column<N>>::Elem]
+      where S: ColumnMut<N> {
+        ColumnMut::<N>::column_mut(&mut self.soa)
+    }
+
+    /// Returns slices over the SoA's other arrays.
+    #[inline]
+    pub fn as_slices(&self) -> S::Slices<'_> {
+        self.soa.as_slices()
+    }
+
+    /// Returns mutable slices over the SoA's other arrays.
+    #[inline]
+    pub fn as_mut_slices(&mut self) -> S::MutSlices<'_> {
+        self.soa.as_mut_slices()
+    }
+
+    /// Consumes the SoA, returning its other arrays and its bits.
+    #[inline]
+    pub fn into_parts(self) -> (S, Bitmask) {
+        (self.soa, self.bits)
+    }
+}
+
+impl<S: Table> Default for BitSoa<S> {
+    fn default() -> BitSoa<S> { BitSoa::new() }
+}
+
+impl<S: Table + Debug> Debug for BitSoa<S> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_struct("BitSoa")
+         .field("soa", &self.soa)
+         .field("bits", &self.bits)
+         .finish()
+    }
+}
@@ -0,0 +1,136 @@
+//! Views of SoA arrays of plain-old-data as raw bytes, via `bytemuck`.
+
+use core::fmt;
+use std::error::Error;
+
+use bytemuck::{self, Pod, PodCastError};
+
+use column::Column;
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+
+/// The reasons a set of byte slices can't be turned into an SoA.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ByteSliceError {
+    /// One of the slices was misaligned, or not a whole number of elements.
+    Cast(PodCastError),
+    /// The slices held different numbers of elements.
+    UnequalLengths,
+}
+
+impl fmt::Display for ByteSliceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ByteSliceError::Cast(e) => write!(f, "invalid SoA array bytes: {:?}", e),
+            ByteSliceError::UnequalLengths => f.write_str("SoA arrays have unequal lengths"),
+        }
+    }
+}
+
+impl Error for ByteSliceError {}
+
+impl From<PodCastError> for ByteSliceError {
+    fn from(e: PodCastError) -> ByteSliceError {
+        ByteSliceError::Cast(e)
+    }
+}
+
+fn cast<T: Pod>(bytes: &[u8]) -> Result<&[T], ByteSliceError> {
+    Ok(try!(bytemuck::try_cast_slice(bytes)))
+}
+
+fn check_lengths(lens: &[usize]) -> Result<(), ByteSliceError> {
+    if lens.iter().any(|&len| len != lens[0]) {
+        return Err(ByteSliceError::UnequalLengths);
+    }
+
+    Ok(())
+}
+
+macro_rules! column_bytes {
+    ($soa:ident<$($ty:ident),+>) => {
+        impl<$($ty),+> $soa<$($ty),+> {
+            /// Returns the bytes of the `N`th array of the SoA.
+            #[inline]
+            pub fn column_bytes<const N: usize>(&self) -> &[u8]
+              where Self: Column<N>, <Self as Column<N>>::Elem: Pod {
+                bytemuck::cast_slice(Column::<N>::column(self))
+            }
+        }
+    }
+}
+
+column_bytes!(Soa2<A, B>);
+column_bytes!(Soa3<A, B, C>);
+column_bytes!(Soa4<A, B, C, D>);
+
+impl<A: Pod, B: Pod> Soa2<A, B> {
+    /// Returns the bytes of each of the SoA's arrays.
+    #[inline]
+    pub fn as_byte_slices<'a>(&'a self) -> (&'a [u8], &'a [u8]) {
+        let (d0, d1) = self.as_slices();
+        (bytemuck::cast_slice(d0), bytemuck::cast_slice(d1))
+    }
+
+    /// Constructs a `Soa2` by copying elements out of byte slices.
+    ///
+    /// Returns an error if any slice is misaligned for its element type or
+    /// isn't a whole number of elements long, or if the slices don't all hold
+    /// the same number of elements.
+    pub fn from_byte_slices(b0: &[u8], b1: &[u8]) -> Result<Soa2<A, B>, ByteSliceError> {
+        let x0: &[A] = try!(cast(b0));
+        let x1: &[B] = try!(cast(b1));
+        try!(check_lengths(&[x0.len(), x1.len()]));
+
+        unsafe { Ok(Soa2::from_raw_bufs(x0.as_ptr(), x1.as_ptr(), x0.len())) }
+    }
+}
+
+impl<A: Pod, B: Pod, C: Pod> Soa3<A, B, C> {
+    /// Returns the bytes of each of the SoA's arrays.
+    #[inline]
+    pub fn as_byte_slices<'a>(&'a self) -> (&'a [u8], &'a [u8], &'a [u8]) {
+        let (d0, d1, d2) = self.as_slices();
+        (bytemuck::cast_slice(d0), bytemuck::cast_slice(d1), bytemuck::cast_slice(d2))
+    }
+
+    /// Constructs a `Soa3` by copying elements out of byte slices.
+    ///
+    /// Returns an error if any slice is misaligned for its element type or
+    /// isn't a whole number of elements long, or if the slices don't all hold
+    /// the same number of elements.
+    pub fn from_byte_slices(b0: &[u8], b1: &[u8], b2: &[u8]) -> Result<Soa3<A, B, C>, ByteSliceError> {
+        let x0: &[A] = try!(cast(b0));
+        let x1: &[B] = try!(cast(b1));
+        let x2: &[C] = try!(cast(b2));
+        try!(check_lengths(&[x0.len(), x1.len(), x2.len()]));
+
+        unsafe { Ok(Soa3::from_raw_bufs(x0.as_ptr(), x1.as_ptr(), x2.as_ptr(), x0.len())) }
+    }
+}
+
+impl<A: Pod, B: Pod, C: Pod, D: Pod> Soa4<A, B, C, D> {
+    /// Returns the bytes of each of the SoA's arrays.
+    #[inline]
+    pub fn as_byte_slices<'a>(&'a self) -> (&'a [u8], &'a [u8], &'a [u8], &'a [u8]) {
+        let (d0, d1, d2, d3) = self.as_slices();
+        (bytemuck::cast_slice(d0), bytemuck::cast_slice(d1),
+         bytemuck::cast_slice(d2), bytemuck::cast_slice(d3))
+    }
+
+    /// Constructs a `Soa4` by copying elements out of byte slices.
+    ///
+    /// Returns an error if any slice is misaligned for its element type or
+    /// isn't a whole number of elements long, or if the slices don't all hold
+    /// the same number of elements.
+    pub fn from_byte_slices(b0: &[u8], b1: &[u8], b2: &[u8], b3: &[u8]) -> Result<Soa4<A, B, C, D>, ByteSliceError> {
+        let x0: &[A] = try!(cast(b0));
+        let x1: &[B] = try!(cast(b1));
+        let x2: &[C] = try!(cast(b2));
+        let x3: &[D] = try!(cast(b3));
+        try!(check_lengths(&[x0.len(), x1.len(), x2.len(), x3.len()]));
+
+        unsafe { Ok(Soa4::from_raw_bufs(x0.as_ptr(), x1.as_ptr(), x2.as_ptr(), x3.as_ptr(), x0.len())) }
+    }
+}
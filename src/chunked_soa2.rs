@@ -0,0 +1,293 @@
+use core::fmt::{Debug, Formatter, Result};
+use core::iter;
+use core::mem;
+use core::slice;
+
+use soa2::Soa2;
+
+type PageIter<'a, A, B> = iter::Zip<slice::Iter<'a, A>, slice::Iter<'a, B>>;
+
+/// An iterator over the elements of a `ChunkedSoa2`, as tuples of references.
+pub type ZipIter<'a, A, B> =
+    iter::FlatMap<slice::Iter<'a, Soa2<A, B>>, PageIter<'a, A, B>,
+                  fn(&'a Soa2<A, B>) -> PageIter<'a, A, B>>;
+
+/// A struct-of-2-arrays type which stores its rows in fixed-size pages, and
+/// never moves a row once it has been pushed.
+///
+/// Each page is a `Soa2` allocated with room for `page_size` rows, so its
+/// arrays are aligned to 16-bytes like any other. When the last page is full,
+/// `push` allocates a new one instead of reallocating and copying everything
+/// that came before, so pushes take O(1) time without the occasional spike,
+/// and pointers into existing rows stay valid until those rows are removed.
+///
+/// The rows can be processed in bulk a page at a time, through `pages`.
+///
+/// A page emptied by `pop` or `truncate` is kept as a spare, so popping and
+/// pushing back and forth across a page boundary doesn't allocate and free a
+/// page every time. `shrink_to_fit` frees it.
+pub struct ChunkedSoa2<A, B> {
+    pages:     Vec<Soa2<A, B>>,
+    spare:     Option<Soa2<A, B>>,
+    page_size: usize,
+    len:       usize,
+}
+
+impl<A, B> ChunkedSoa2<A, B> {
+    /// Constructs a new, empty `ChunkedSoa2` whose pages hold `page_size` rows
+    /// each.
+    ///
+    /// The SoA will not allocate until elements are pushed onto it.
+    ///
+    /// Panics if `page_size` is zero.
+    #[inline]
+    pub fn new(page_size: usize) -> ChunkedSoa2<A, B> {
+        assert!(page_size != 0, "page size must be nonzero");
+        ChunkedSoa2 { pages: Vec::new(), spare: None, page_size: page_size, len: 0 }
+    }
+
+    /// Returns the number of rows each page holds.
+    #[inline]
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Returns the number of elements in the SoA.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the SoA contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of elements the SoA can hold without allocating
+    /// another page.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        (self.pages.len() + self.spare.is_some() as usize) * self.page_size
+    }
+
+    /// Returns the number of pages holding elements. The spare page, if any,
+    /// isn't counted.
+    #[inline]
+    pub fn num_pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Frees the spare page, if there is one.
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.spare = None;
+    }
+
+    #[inline]
+    fn locate(&self, index: usize) -> (usize, usize) {
+        (index / self.page_size, index % self.page_size)
+    }
+
+    /// Appends an element to the back of the SoA, and returns its index.
+    ///
+    /// This allocates a new page if the last one is full and there is no
+    /// spare, but never moves existing elements.
+    #[inline]
+    pub fn push(&mut self, value: (A, B)) -> usize {
+        let index = self.len;
+        let (page, _) = self.locate(index);
+
+        if page == self.pages.len() {
+            let page_size = self.page_size;
+            let fresh = self.spare.take().unwrap_or_else(|| Soa2::with_capacity(page_size));
+            self.pages.push(fresh);
+        }
+
+        self.pages[page].push(value);
+        self.len += 1;
+        index
+    }
+
+    /// Removes the last element from the SoA and returns it, or `None` if
+    /// empty.
+    ///
+    /// A page becomes the spare once its last element is popped, replacing
+    /// any spare there was.
+    #[inline]
+    pub fn pop(&mut self) -> Option<(A, B)> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.len -= 1;
+        let ret = self.pages.last_mut().and_then(|p| p.pop());
+        if self.pages.last().map_or(false, |p| p.is_empty()) {
+            self.spare = self.pages.pop();
+        }
+        ret
+    }
+
+    /// Returns the `index`th element, or `None` if it is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(&A, &B)> {
+        if index >= self.len {
+            return None;
+        }
+
+        let (page, offset) = self.locate(index);
+        self.pages[page].get(offset)
+    }
+
+    /// Returns the `index`th element mutably, or `None` if it is out of
+    /// bounds.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<(&mut A, &mut B)> {
+        if index >= self.len {
+            return None;
+        }
+
+        let (page, offset) = self.locate(index);
+        self.pages[page].get_mut(offset)
+    }
+
+    /// Removes the `index`th element and returns it, replacing it with the
+    /// last element.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> (A, B) {
+        assert!(index < self.len, "index out of bounds");
+
+        let last = self.pop().unwrap();
+        if index == self.len {
+            return last;
+        }
+
+        let (page, offset) = self.locate(index);
+        let (d0, d1) = self.pages[page].as_mut_slices();
+        (mem::replace(&mut d0[offset], last.0),
+         mem::replace(&mut d1[offset], last.1))
+    }
+
+    /// Shortens the SoA, keeping the first `len` elements and dropping the
+    /// rest. Pages left empty are freed, except for one which is kept as the
+    /// spare.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+
+        let pages = (len + self.page_size - 1) / self.page_size;
+        let mut emptied = self.pages.split_off(pages);
+        if self.spare.is_none() {
+            self.spare = emptied.pop().map(|mut p| { p.clear(); p });
+        }
+        drop(emptied);
+
+        if let Some(last) = self.pages.last_mut() {
+            let keep = len - (pages - 1) * self.page_size;
+            last.truncate(keep);
+        }
+
+        self.len = len;
+    }
+
+    /// Clears the SoA, removing all values and freeing every page but the
+    /// spare.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Returns an iterator over the pages, each as a pair of slices over its
+    /// arrays.
+    ///
+    /// Every page but the last is full.
+    #[inline]
+    pub fn pages(&self) -> Pages<A, B> {
+        Pages { pages: self.pages.iter() }
+    }
+
+    /// Returns an iterator over the pages, each as a pair of mutable slices
+    /// over its arrays.
+    #[inline]
+    pub fn pages_mut(&mut self) -> PagesMut<A, B> {
+        PagesMut { pages: self.pages.iter_mut() }
+    }
+
+    /// Returns an iterator over the SoA's elements, as tuples of references.
+    #[inline]
+    pub fn zip_iter(&self) -> ZipIter<A, B> {
+        fn zip_iter<A, B>(p: &Soa2<A, B>) -> PageIter<A, B> { p.zip_iter() }
+        self.pages.iter().flat_map(zip_iter as fn(&Soa2<A, B>) -> PageIter<A, B>)
+    }
+
+    /// Extends the SoA with the elements yielded by an iterator.
+    pub fn extend<I: IntoIterator<Item=(A, B)>>(&mut self, iterable: I) {
+        for x in iterable {
+            self.push(x);
+        }
+    }
+}
+
+impl<A: Clone, B: Clone> Clone for ChunkedSoa2<A, B> {
+    fn clone(&self) -> ChunkedSoa2<A, B> {
+        let mut ret = ChunkedSoa2::new(self.page_size);
+        ret.extend(self.zip_iter().map(|(a, b)| (a.clone(), b.clone())));
+        ret
+    }
+}
+
+impl<A0, B0, A1, B1> PartialEq<ChunkedSoa2<A1, B1>> for ChunkedSoa2<A0, B0>
+  where A0: PartialEq<A1>, B0: PartialEq<B1> {
+    #[inline]
+    fn eq(&self, other: &ChunkedSoa2<A1, B1>) -> bool {
+        self.len() == other.len()
+        && self.zip_iter().zip(other.zip_iter()).all(
+            |((a0, b0), (a1, b1))| a0 == a1 && b0 == b1)
+    }
+}
+
+impl<A: Debug, B: Debug> Debug for ChunkedSoa2<A, B> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_list().entries(self.zip_iter()).finish()
+    }
+}
+
+/// An iterator over the pages of a `ChunkedSoa2`.
+pub struct Pages<'a, A: 'a, B: 'a> {
+    pages: slice::Iter<'a, Soa2<A, B>>,
+}
+
+impl<'a, A, B> Iterator for Pages<'a, A, B> {
+    type Item = (&'a [A], &'a [B]);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a [A], &'a [B])> {
+        self.pages.next().map(|p| p.as_slices())
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.pages.size_hint()
+    }
+}
+
+/// A mutable iterator over the pages of a `ChunkedSoa2`.
+pub struct PagesMut<'a, A: 'a, B: 'a> {
+    pages: slice::IterMut<'a, Soa2<A, B>>,
+}
+
+impl<'a, A, B> Iterator for PagesMut<'a, A, B> {
+    type Item = (&'a mut [A], &'a mut [B]);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a mut [A], &'a mut [B])> {
+        self.pages.next().map(|p| p.as_mut_slices())
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.pages.size_hint()
+    }
+}
@@ -0,0 +1,224 @@
+//! Access to a single array of an SoA, picked out by its index.
+//!
+//! This lets code that only cares about one array be written once for every
+//! SoA type: `soa.column::<1>()` is the second array of a `Soa2`, `Soa3`, or
+//! `Soa4` alike.
+//!
+//! `ColumnMut` adds mutable access, for the SoA types which can always give it
+//! out. `DropColumn` and `MapColumn` likewise remove or convert an array picked
+//! out by its index.
+
+use core::mem;
+
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+use unadorned::{self, Extent};
+
+/// SoA types whose `N`th array holds `Elem`s.
+pub trait Column<const N: usize> {
+    /// The element type of the array.
+    type Elem;
+
+    /// Returns a slice over the array.
+    fn column(&self) -> &[Self::Elem];
+}
+
+/// SoA types whose `N`th array can be changed in place.
+pub trait ColumnMut<const N: usize>: Column<N> {
+    /// Returns a mutable slice over the array.
+    fn column_mut(&mut self) -> &mut [Self::Elem];
+}
+
+macro_rules! column {
+    ($soa:ident<$($ty:ident),+> $n:tt $elem:ident) => {
+        impl<$($ty),+> Column<$n> for $soa<$($ty),+> {
+            type Elem = $elem;
+
+            #[inline]
+            fn column(&self) -> &[$elem] { self.as_slices().$n }
+        }
+
+        impl<$($ty),+> ColumnMut<$n> for $soa<$($ty),+> {
+            #[inline]
+            fn column_mut(&mut self) -> &mut [$elem] { self.as_mut_slices().$n }
+        }
+    }
+}
+
+column!(Soa2<A, B> 0 A);
+column!(Soa2<A, B> 1 B);
+
+column!(Soa3<A, B, C> 0 A);
+column!(Soa3<A, B, C> 1 B);
+column!(Soa3<A, B, C> 2 C);
+
+column!(Soa4<A, B, C, D> 0 A);
+column!(Soa4<A, B, C, D> 1 B);
+column!(Soa4<A, B, C, D> 2 C);
+column!(Soa4<A, B, C, D> 3 D);
+
+macro_rules! column_accessors {
+    ($soa:ident<$($ty:ident),+>) => {
+        impl<$($ty),+> $soa<$($ty),+> {
+            /// Returns a slice over the `N`th array of the SoA.
+            #[inline]
+            pub fn column<const N: usize>(&self) -> &[<Self as Column<N>>::Elem]
+              where Self: Column<N> {
+                Column::<N>::column(self)
+            }
+
+            /// Returns a mutable slice over the `N`th array of the SoA.
+            #[inline]
+            pub fn column_mut<const N: usize>(&mut self) -> &mut [<Self as Column<N>>::Elem]
+              where Self: ColumnMut<N> {
+                ColumnMut::<N>::column_mut(self)
+            }
+        }
+    }
+}
+
+column_accessors!(Soa2<A, B>);
+column_accessors!(Soa3<A, B, C>);
+column_accessors!(Soa4<A, B, C, D>);
+
+/// SoA types which can have their `N`th array removed.
+pub trait DropColumn<const N: usize> {
+    /// The SoA of the remaining arrays.
+    type Output;
+
+    /// Drops the `N`th array and its elements, returning an SoA of the others.
+    /// The remaining arrays are moved, not copied.
+    fn drop_column(self) -> Self::Output;
+}
+
+macro_rules! drop_column {
+    ($soa:ident<$($ty:ident),+> $n:tt => $out:ident<$($oty:ident),+> [$($keep:tt),+]) => {
+        impl<$($ty),+> DropColumn<$n> for $soa<$($ty),+> {
+            type Output = $out<$($oty),+>;
+
+            fn drop_column(mut self) -> $out<$($oty),+> {
+                let e = Extent { len: self.len(), cap: self.capacity(), lanes: self.lanes() };
+
+                unsafe {
+                    let ptrs = self.as_mut_ptrs();
+                    mem::forget(self);
+
+                    unadorned::drop_column(ptrs.$n, &e);
+
+                    let mut ret = $out::from_raw_parts($(ptrs.$keep),+, e.len, e.cap);
+                    ret.set_lanes(e.lanes);
+                    ret
+                }
+            }
+        }
+    }
+}
+
+drop_column!(Soa3<A, B, C> 0 => Soa2<B, C> [1, 2]);
+drop_column!(Soa3<A, B, C> 1 => Soa2<A, C> [0, 2]);
+drop_column!(Soa3<A, B, C> 2 => Soa2<A, B> [0, 1]);
+
+drop_column!(Soa4<A, B, C, D> 0 => Soa3<B, C, D> [1, 2, 3]);
+drop_column!(Soa4<A, B, C, D> 1 => Soa3<A, C, D> [0, 2, 3]);
+drop_column!(Soa4<A, B, C, D> 2 => Soa3<A, B, D> [0, 1, 3]);
+drop_column!(Soa4<A, B, C, D> 3 => Soa3<A, B, C> [0, 1, 2]);
+
+macro_rules! drop_column_accessor {
+    ($soa:ident<$($ty:ident),+>) => {
+        impl<$($ty),+> $soa<$($ty),+> {
+            /// Drops the `N`th array and its elements, returning an SoA of the
+            /// others. The remaining arrays are moved, not copied.
+            #[inline]
+            pub fn drop_column<const N: usize>(self) -> <Self as DropColumn<N>>::Output
+              where Self: DropColumn<N> {
+                DropColumn::<N>::drop_column(self)
+            }
+        }
+    }
+}
+
+drop_column_accessor!(Soa3<A, B, C>);
+drop_column_accessor!(Soa4<A, B, C, D>);
+
+/// SoA types whose `N`th array can be mapped to a new element type `U`.
+pub trait MapColumn<const N: usize, U>: Column<N> {
+    /// The SoA with `U`s in place of the `N`th array.
+    type Output;
+
+    /// Consumes the SoA, passing each element of the `N`th array through `f`.
+    ///
+    /// The other arrays are moved into the new SoA untouched. If `U` is the
+    /// same size as the old element type, the `N`th array's allocation is
+    /// reused; otherwise a new one is made with the same capacity.
+    ///
+    /// If `f` panics, the elements of the other arrays are leaked.
+    fn map_column<F: FnMut(<Self as Column<N>>::Elem) -> U>(self, f: F) -> Self::Output;
+}
+
+macro_rules! map_column {
+    ($soa:ident<$($ty:ident),+> $n:tt => $out:ident<$($oty:ident),+>
+     [$($p:ident),+] $q:ident => ($($r:ident),+)) => {
+        impl<$($ty),+, U> MapColumn<$n, U> for $soa<$($ty),+> {
+            type Output = $out<$($oty),+>;
+
+            fn map_column<F: FnMut(<Self as Column<$n>>::Elem) -> U>(mut self, f: F) -> $out<$($oty),+> {
+                let e = Extent { len: self.len(), cap: self.capacity(), lanes: self.lanes() };
+
+                let is_boring = true $(&& mem::size_of::<$ty>() == 0)+;
+                let cap =
+                    if is_boring && mem::size_of::<U>() != 0 {
+                        // every array was zero-sized, so the capacity was
+                        // meaningless, and may even be less than the length
+                        e.len
+                    } else {
+                        e.cap
+                    };
+
+                unsafe {
+                    let ($($p),+) = self.as_mut_ptrs();
+
+                    // If `f` panics, `self` frees the arrays without dropping
+                    // anything twice.
+                    self.set_len(0);
+                    let $q = unadorned::map_column($q, &e, cap, f);
+                    mem::forget(self);
+
+                    let mut ret = $out::from_raw_parts($($r),+, e.len, cap);
+                    ret.set_lanes(e.lanes);
+                    ret
+                }
+            }
+        }
+    }
+}
+
+map_column!(Soa2<A, B> 0 => Soa2<U, B> [p0, p1] p0 => (p0, p1));
+map_column!(Soa2<A, B> 1 => Soa2<A, U> [p0, p1] p1 => (p0, p1));
+
+map_column!(Soa3<A, B, C> 0 => Soa3<U, B, C> [p0, p1, p2] p0 => (p0, p1, p2));
+map_column!(Soa3<A, B, C> 1 => Soa3<A, U, C> [p0, p1, p2] p1 => (p0, p1, p2));
+map_column!(Soa3<A, B, C> 2 => Soa3<A, B, U> [p0, p1, p2] p2 => (p0, p1, p2));
+
+map_column!(Soa4<A, B, C, D> 0 => Soa4<U, B, C, D> [p0, p1, p2, p3] p0 => (p0, p1, p2, p3));
+map_column!(Soa4<A, B, C, D> 1 => Soa4<A, U, C, D> [p0, p1, p2, p3] p1 => (p0, p1, p2, p3));
+map_column!(Soa4<A, B, C, D> 2 => Soa4<A, B, U, D> [p0, p1, p2, p3] p2 => (p0, p1, p2, p3));
+map_column!(Soa4<A, B, C, D> 3 => Soa4<A, B, C, U> [p0, p1, p2, p3] p3 => (p0, p1, p2, p3));
+
+macro_rules! map_column_accessor {
+    ($soa:ident<$($ty:ident),+>) => {
+        impl<$($ty),+> $soa<$($ty),+> {
+            /// Consumes the SoA, passing each element of the `N`th array
+            /// through `f`. See `MapColumn`.
+            #[inline]
+            pub fn map_column<const N: usize, U, F>(self, f: F) -> <Self as MapColumn<N, U>>::Output
+              where Self: MapColumn<N, U>, F: FnMut(<Self as Column<N>>::Elem) -> U {
+                MapColumn::<N, U>::map_column(self, f)
+            }
+        }
+    }
+}
+
+map_column_accessor!(Soa2<A, B>);
+map_column_accessor!(Soa3<A, B, C>);
+map_column_accessor!(Soa4<A, B, C, D>);
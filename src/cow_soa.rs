@@ -0,0 +1,357 @@
+//! Struct-of-arrays types whose arrays are shared between clones, and only
+//! copied when one of them is changed.
+//!
+//! Cloning a `CowSoa4` just bumps four reference counts. The first mutable
+//! access to an array through a clone copies that array, and only that array,
+//! so a fork of a wide table which only ever touches one column only pays for
+//! one column.
+
+use core::fmt::{Debug, Formatter, Result};
+use core::mem;
+use core::ptr;
+use core::usize;
+use std::sync::Arc;
+
+use column::{Column, ColumnMut};
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+use unadorned::{self, Extent, Unadorned};
+
+/// A single array with its own extent, which is what gets shared.
+struct Col<T> {
+    d: Unadorned<T>,
+    e: Extent,
+}
+
+impl<T> Col<T> {
+    fn new() -> Col<T> {
+        unsafe {
+            let (d, du) = Unadorned::new();
+            let mut e = unadorned::new_update(&[du]);
+
+            // zero-size types consume no memory, so never need to grow
+            if d.is_boring() { e.cap = usize::MAX }
+
+            Col { d: d, e: e }
+        }
+    }
+
+    /// Takes ownership of an allocation made by one of the SoA types.
+    unsafe fn from_raw_parts(ptr: *mut T, len: usize, cap: usize) -> Col<T> {
+        let (d, du) = Unadorned::from_raw_parts(ptr);
+        let mut e = unadorned::from_raw_parts_update(&[du], len, cap);
+
+        // a boring SoA's capacity may be less than its length
+        if d.is_boring() { e.cap = usize::MAX }
+
+        Col { d: d, e: e }
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        unsafe { self.d.as_slice(self.e.len) }
+    }
+
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { self.d.as_mut_slice(self.e.len) }
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        unsafe {
+            if let Some(space) = unadorned::calc_reserve_space(&self.e, additional) {
+                let du = self.d.reserve(&self.e, &space);
+                unadorned::reserve_update(&[du], space, &mut self.e);
+            }
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, x: T) {
+        unsafe {
+            let du = self.d.push(x, &self.e);
+            unadorned::push_update(&[du], &mut self.e);
+        }
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Option<T> {
+        if self.e.len == 0 {
+            None
+        } else {
+            self.e.len -= 1;
+            unsafe { Some(ptr::read(self.d.as_ptr().offset(self.e.len as isize))) }
+        }
+    }
+
+    fn truncate(&mut self, len: usize) {
+        if len >= self.e.len { return }
+
+        unsafe {
+            let du = self.d.truncate(len, &self.e);
+            unadorned::truncate_update(&[du], len, &mut self.e);
+        }
+    }
+}
+
+impl<T: Clone> Clone for Col<T> {
+    fn clone(&self) -> Col<T> {
+        let mut ret = Col::new();
+        unsafe {
+            let x = self.as_slice();
+            let space = unadorned::calc_reserve_exact_space(&ret.e, x.len());
+            let du = ret.d.push_all(x, &ret.e, &space);
+            unadorned::push_all_update(&[du], &mut ret.e, x.len(), space);
+        }
+        ret
+    }
+}
+
+impl<T> Drop for Col<T> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.e.cap != 0 {
+            unsafe { self.d.drop(&self.e) }
+        }
+    }
+}
+
+macro_rules! cow_soa {
+    ($cow:ident, $soa:ident, [$($d:ident: $ty:ident),+]) => {
+        /// A struct-of-arrays type whose arrays are reference-counted and copied
+        /// on write. See the module documentation.
+        ///
+        /// Shared arrays are never changed: every method that mutates an array
+        /// first makes this SoA its sole owner, cloning it if need be.
+        /// `column_mut` does so for one array, and methods which add or remove
+        /// rows do so for all of them.
+        ///
+        /// All data is aligned to 16-bytes.
+        pub struct $cow<$($ty),+> {
+            $($d: Arc<Col<$ty>>),+
+        }
+
+        impl<$($ty),+> $cow<$($ty),+> {
+            /// Constructs a new, empty SoA.
+            ///
+            /// The SoA will not allocate until elements are pushed onto it.
+            #[inline]
+            pub fn new() -> $cow<$($ty),+> {
+                $cow { $($d: Arc::new(Col::new())),+ }
+            }
+
+            /// Returns the number of tuples stored in the SoA.
+            #[inline]
+            pub fn len(&self) -> usize {
+                self.d0.e.len
+            }
+
+            /// Returns `true` if the SoA contains no elements.
+            #[inline]
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// Returns the number of arrays which are currently shared with
+            /// another SoA.
+            #[inline]
+            pub fn shared_columns(&self) -> usize {
+                let mut n = 0;
+                $(if Arc::strong_count(&self.$d) > 1 { n += 1 })+
+                n
+            }
+
+            /// Returns slices over the SoA's elements.
+            #[inline]
+            pub fn as_slices(&self) -> ($(&[$ty]),+) {
+                ($(self.$d.as_slice()),+)
+            }
+
+            /// Returns the `index`th row, or `None` if it is out of bounds.
+            #[inline]
+            pub fn get(&self, index: usize) -> Option<($(&$ty),+)> {
+                if index >= self.len() { return None }
+                unsafe { Some(($(self.$d.as_slice().get_unchecked(index)),+)) }
+            }
+
+            /// Returns a slice over the `N`th array of the SoA.
+            #[inline]
+            pub fn column<const N: usize>(&self) -> &[<Self as Column<N>>::Elem]
+              where Self: Column<N> {
+                Column::<N>::column(self)
+            }
+
+            /// Returns a mutable slice over the `N`th array of the SoA, first
+            /// copying it if it is shared.
+            ///
+            /// The SoA's other arrays stay shared.
+            #[inline]
+            pub fn column_mut<const N: usize>(&mut self) -> &mut [<Self as Column<N>>::Elem]
+              where Self: ColumnMut<N> {
+                ColumnMut::<N>::column_mut(self)
+            }
+        }
+
+        impl<$($ty: Clone),+> $cow<$($ty),+> {
+            /// Returns mutable slices over the SoA's elements, first copying
+            /// every array which is shared.
+            #[inline]
+            pub fn as_mut_slices(&mut self) -> ($(&mut [$ty]),+) {
+                ($(Arc::make_mut(&mut self.$d).as_mut_slice()),+)
+            }
+
+            /// Returns the `index`th row mutably, or `None` if it is out of
+            /// bounds. This copies every array which is shared.
+            #[inline]
+            pub fn get_mut(&mut self, index: usize) -> Option<($(&mut $ty),+)> {
+                if index >= self.len() { return None }
+                let ($($d),+) = self.as_mut_slices();
+                unsafe { Some(($($d.get_unchecked_mut(index)),+)) }
+            }
+
+            /// Makes this SoA the sole owner of every array, copying the
+            /// shared ones.
+            ///
+            /// Methods which change the number of rows call this before they
+            /// touch any array, so a panicking `Clone` can't leave the arrays
+            /// with different lengths.
+            #[inline]
+            fn make_mut(&mut self) -> ($(&mut Col<$ty>),+) {
+                ($(Arc::make_mut(&mut self.$d)),+)
+            }
+
+            /// Appends an element to the back of the SoA.
+            #[inline]
+            pub fn push(&mut self, value: ($($ty),+)) {
+                {
+                    let ($($d),+) = self.make_mut();
+                    // Once every array has room, none of the pushes can fail.
+                    $($d.reserve(1);)+
+                }
+
+                let ($($d),+) = value;
+                $(Arc::get_mut(&mut self.$d).unwrap().push($d);)+
+            }
+
+            /// Removes the last element from the SoA and returns it, or `None`
+            /// if empty.
+            #[inline]
+            pub fn pop(&mut self) -> Option<($($ty),+)> {
+                if self.is_empty() { return None }
+                let ($($d),+) = self.make_mut();
+                Some(($($d.pop().unwrap()),+))
+            }
+
+            /// Removes an element from anywhere in the SoA and returns it,
+            /// replacing it with the last element.
+            ///
+            /// Panics if `index` is out of bounds.
+            pub fn swap_remove(&mut self, index: usize) -> ($($ty),+) {
+                let last = self.len() - 1;
+                {
+                    let ($($d),+) = self.as_mut_slices();
+                    $($d.swap(index, last);)+
+                }
+                self.pop().unwrap()
+            }
+
+            /// Shortens the SoA, keeping the first `len` elements and dropping
+            /// the rest.
+            pub fn truncate(&mut self, len: usize) {
+                if len >= self.len() { return }
+                let ($($d),+) = self.make_mut();
+                $($d.truncate(len);)+
+            }
+
+            /// Clears the SoA, removing all values.
+            ///
+            /// Shared arrays are let go of rather than copied and cleared.
+            pub fn clear(&mut self) {
+                *self = $cow::new();
+            }
+
+            /// Copies the SoA's elements into a new, ordinary SoA.
+            pub fn to_soa(&self) -> $soa<$($ty),+> {
+                let mut ret = $soa::with_capacity(self.len());
+                let ($($d),+) = self.as_slices();
+                ret.push_all($($d),+);
+                ret
+            }
+        }
+
+        impl<$($ty),+> Clone for $cow<$($ty),+> {
+            /// Makes another SoA sharing all of this one's arrays. This takes
+            /// O(1) time.
+            #[inline]
+            fn clone(&self) -> $cow<$($ty),+> {
+                $cow { $($d: self.$d.clone()),+ }
+            }
+        }
+
+        impl<$($ty),+> From<$soa<$($ty),+>> for $cow<$($ty),+> {
+            /// Moves the arrays of an ordinary SoA into one which can share
+            /// them, without copying.
+            fn from(mut soa: $soa<$($ty),+>) -> $cow<$($ty),+> {
+                let len = soa.len();
+                let cap = soa.capacity();
+                let ($($d),+) = soa.as_mut_ptrs();
+                mem::forget(soa);
+
+                unsafe {
+                    $cow { $($d: Arc::new(Col::from_raw_parts($d, len, cap))),+ }
+                }
+            }
+        }
+
+        impl<$($ty),+> Default for $cow<$($ty),+> {
+            fn default() -> $cow<$($ty),+> { $cow::new() }
+        }
+
+        impl<$($ty: PartialEq),+> PartialEq for $cow<$($ty),+> {
+            #[inline]
+            fn eq(&self, other: &$cow<$($ty),+>) -> bool {
+                self.as_slices() == other.as_slices()
+            }
+        }
+
+        impl<$($ty: Debug),+> Debug for $cow<$($ty),+> {
+            fn fmt(&self, f: &mut Formatter) -> Result {
+                Debug::fmt(&self.as_slices(), f)
+            }
+        }
+    }
+}
+
+cow_soa!(CowSoa2, Soa2, [d0: A, d1: B]);
+cow_soa!(CowSoa3, Soa3, [d0: A, d1: B, d2: C]);
+cow_soa!(CowSoa4, Soa4, [d0: A, d1: B, d2: C, d3: D]);
+
+macro_rules! cow_column {
+    ($cow:ident<$($ty:ident),+> $n:tt $d:ident $elem:ident) => {
+        impl<$($ty),+> Column<$n> for $cow<$($ty),+> {
+            type Elem = $elem;
+
+            #[inline]
+            fn column(&self) -> &[$elem] { self.$d.as_slice() }
+        }
+
+        // Only writing may need to copy the array.
+        impl<$($ty),+> ColumnMut<$n> for $cow<$($ty),+> where $elem: Clone {
+            #[inline]
+            fn column_mut(&mut self) -> &mut [$elem] { Arc::make_mut(&mut self.$d).as_mut_slice() }
+        }
+    }
+}
+
+cow_column!(CowSoa2<A, B> 0 d0 A);
+cow_column!(CowSoa2<A, B> 1 d1 B);
+
+cow_column!(CowSoa3<A, B, C> 0 d0 A);
+cow_column!(CowSoa3<A, B, C> 1 d1 B);
+cow_column!(CowSoa3<A, B, C> 2 d2 C);
+
+cow_column!(CowSoa4<A, B, C, D> 0 d0 A);
+cow_column!(CowSoa4<A, B, C, D> 1 d1 B);
+cow_column!(CowSoa4<A, B, C, D> 2 d2 C);
+cow_column!(CowSoa4<A, B, C, D> 3 d3 D);
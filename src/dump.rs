@@ -0,0 +1,344 @@
+//! A versioned binary format for SoAs of plain-old-data, which can be written
+//! in one pass and opened again with `mmap` without copying.
+//!
+//! A dump is laid out as:
+//!
+//! | bytes | contents                                             |
+//! |-------|------------------------------------------------------|
+//! | 8     | the magic string `b"SOADUMP\0"`                      |
+//! | 4     | format version, little endian (currently `1`)        |
+//! | 4     | `0x01020304` in the writer's byte order              |
+//! | 8     | row count, little endian                             |
+//! | 4     | column count, little endian                          |
+//! | 8 × n | element size and alignment of each column, little endian `u32`s |
+//!
+//! followed by each column's raw bytes, in the writer's byte order. Every
+//! column starts on a 16-byte boundary, so the mapped columns are aligned the
+//! same way as the arrays of a heap allocated SoA.
+
+use core::marker::PhantomData;
+use core::mem;
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use bytemuck::{self, Pod};
+use memmap2::Mmap;
+
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+
+const MAGIC: &'static [u8; 8] = b"SOADUMP\0";
+const VERSION: u32 = 1;
+const BYTE_ORDER: u32 = 0x01020304;
+const COLUMN_ALIGN: usize = 16;
+
+/// The element size and alignment of a column.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Layout {
+    size:  u32,
+    align: u32,
+}
+
+impl Layout {
+    fn of<T>() -> Layout {
+        Layout {
+            size:  mem::size_of::<T>() as u32,
+            align: mem::align_of::<T>() as u32,
+        }
+    }
+}
+
+fn align_up(n: usize) -> usize {
+    (n + COLUMN_ALIGN - 1) & !(COLUMN_ALIGN - 1)
+}
+
+fn header_len(columns: usize) -> usize {
+    8 + 4 + 4 + 8 + 4 + 8 * columns
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Returns the byte offset of each column, and the total length of the dump,
+/// or `None` if that doesn't fit in a `usize`.
+fn column_offsets(rows: usize, layouts: &[Layout]) -> Option<(Vec<usize>, usize)> {
+    let mut offsets = Vec::with_capacity(layouts.len());
+    let mut end = header_len(layouts.len());
+
+    for l in layouts {
+        let start = align_up(end);
+        offsets.push(start);
+        end = match rows.checked_mul(l.size as usize).and_then(|n| n.checked_add(start)) {
+            Some(end) if end <= usize::max_value() - COLUMN_ALIGN => end,
+            _ => return None,
+        };
+    }
+
+    Some((offsets, end))
+}
+
+fn write_columns<W: Write>(mut w: W, rows: usize, layouts: &[Layout], columns: &[&[u8]]) -> io::Result<()> {
+    try!(w.write_all(MAGIC));
+    try!(w.write_all(&VERSION.to_le_bytes()));
+    try!(w.write_all(&BYTE_ORDER.to_ne_bytes()));
+    try!(w.write_all(&(rows as u64).to_le_bytes()));
+    try!(w.write_all(&(layouts.len() as u32).to_le_bytes()));
+
+    for l in layouts {
+        try!(w.write_all(&l.size.to_le_bytes()));
+        try!(w.write_all(&l.align.to_le_bytes()));
+    }
+
+    let zeros = [0u8; COLUMN_ALIGN];
+    let mut written = header_len(layouts.len());
+
+    for col in columns {
+        let pad = align_up(written) - written;
+        try!(w.write_all(&zeros[..pad]));
+        try!(w.write_all(col));
+        written += pad + col.len();
+    }
+
+    w.flush()
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    let mut b = [0u8; 4];
+    b.copy_from_slice(&bytes[at..at + 4]);
+    u32::from_le_bytes(b)
+}
+
+/// Checks the header of a dump against the expected column layouts, and
+/// returns the row count and the byte offset of each column.
+fn parse_header(bytes: &[u8], layouts: &[Layout]) -> io::Result<(usize, Vec<usize>)> {
+    if bytes.len() < header_len(layouts.len()) || &bytes[..8] != &MAGIC[..] {
+        return Err(invalid("not an SoA dump"));
+    }
+
+    if read_u32(bytes, 8) != VERSION {
+        return Err(invalid("unsupported SoA dump version"));
+    }
+
+    let mut order = [0u8; 4];
+    order.copy_from_slice(&bytes[12..16]);
+    if u32::from_ne_bytes(order) != BYTE_ORDER {
+        return Err(invalid("SoA dump was written with a different byte order"));
+    }
+
+    let mut rows = [0u8; 8];
+    rows.copy_from_slice(&bytes[16..24]);
+    let rows = u64::from_le_bytes(rows);
+
+    if read_u32(bytes, 24) as usize != layouts.len() {
+        return Err(invalid("SoA dump has the wrong number of columns"));
+    }
+
+    for (i, expected) in layouts.iter().enumerate() {
+        let found = Layout {
+            size:  read_u32(bytes, 28 + 8 * i),
+            align: read_u32(bytes, 32 + 8 * i),
+        };
+
+        if found != *expected {
+            return Err(invalid("SoA dump column types don't match"));
+        }
+    }
+
+    if rows > usize::max_value() as u64 {
+        return Err(invalid("SoA dump is too large"));
+    }
+    let rows = rows as usize;
+
+    let offsets =
+        match column_offsets(rows, layouts) {
+            Some((offsets, end)) if end <= bytes.len() => offsets,
+            _ => return Err(invalid("SoA dump is truncated")),
+        };
+
+    Ok((rows, offsets))
+}
+
+/// Reinterprets one column of a mapped dump.
+fn column<'a, T: Pod>(bytes: &'a [u8], offset: usize, rows: usize) -> &'a [T] {
+    let len = rows * mem::size_of::<T>();
+    bytemuck::cast_slice(&bytes[offset..offset + len])
+}
+
+fn map(path: &Path) -> io::Result<Mmap> {
+    let file = try!(File::open(path));
+    // The map is only ever read, but it is up to the caller not to modify the
+    // file underneath it.
+    unsafe { Mmap::map(&file) }
+}
+
+impl<A: Pod, B: Pod> Soa2<A, B> {
+    /// Writes the SoA to `w` in the dump format, in a single pass.
+    pub fn dump<W: Write>(&self, w: W) -> io::Result<()> {
+        let (b0, b1) = self.as_byte_slices();
+        write_columns(w, self.len(), &[Layout::of::<A>(), Layout::of::<B>()], &[b0, b1])
+    }
+}
+
+impl<A: Pod, B: Pod, C: Pod> Soa3<A, B, C> {
+    /// Writes the SoA to `w` in the dump format, in a single pass.
+    pub fn dump<W: Write>(&self, w: W) -> io::Result<()> {
+        let (b0, b1, b2) = self.as_byte_slices();
+        write_columns(w, self.len(), &[Layout::of::<A>(), Layout::of::<B>(), Layout::of::<C>()],
+                      &[b0, b1, b2])
+    }
+}
+
+impl<A: Pod, B: Pod, C: Pod, D: Pod> Soa4<A, B, C, D> {
+    /// Writes the SoA to `w` in the dump format, in a single pass.
+    pub fn dump<W: Write>(&self, w: W) -> io::Result<()> {
+        let (b0, b1, b2, b3) = self.as_byte_slices();
+        write_columns(w, self.len(), &[Layout::of::<A>(), Layout::of::<B>(), Layout::of::<C>(), Layout::of::<D>()],
+                      &[b0, b1, b2, b3])
+    }
+}
+
+/// A read-only `Soa2` backed by a memory mapped dump.
+pub struct MappedSoa2<A, B> {
+    map:     Mmap,
+    len:     usize,
+    offsets: Vec<usize>,
+    _marker: PhantomData<(A, B)>,
+}
+
+impl<A: Pod, B: Pod> MappedSoa2<A, B> {
+    /// Maps the dump at `path`, checking that its columns are `A` and `B`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<MappedSoa2<A, B>> {
+        let map = try!(self::map(path.as_ref()));
+        let (len, offsets) = try!(parse_header(&map, &[Layout::of::<A>(), Layout::of::<B>()]));
+
+        Ok(MappedSoa2 { map: map, len: len, offsets: offsets, _marker: PhantomData })
+    }
+
+    /// Returns the number of tuples stored in the SoA.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the SoA contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns slices over the SoA's elements.
+    #[inline]
+    pub fn as_slices<'a>(&'a self) -> (&'a [A], &'a [B]) {
+        (column(&self.map, self.offsets[0], self.len),
+         column(&self.map, self.offsets[1], self.len))
+    }
+
+    /// Copies the mapped elements into a heap allocated `Soa2`.
+    pub fn to_soa(&self) -> Soa2<A, B> {
+        let (d0, d1) = self.as_slices();
+        let mut ret = Soa2::new();
+        ret.push_all(d0, d1);
+        ret
+    }
+}
+
+/// A read-only `Soa3` backed by a memory mapped dump.
+pub struct MappedSoa3<A, B, C> {
+    map:     Mmap,
+    len:     usize,
+    offsets: Vec<usize>,
+    _marker: PhantomData<(A, B, C)>,
+}
+
+impl<A: Pod, B: Pod, C: Pod> MappedSoa3<A, B, C> {
+    /// Maps the dump at `path`, checking that its columns are `A`, `B`, and
+    /// `C`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<MappedSoa3<A, B, C>> {
+        let map = try!(self::map(path.as_ref()));
+        let (len, offsets) =
+            try!(parse_header(&map, &[Layout::of::<A>(), Layout::of::<B>(), Layout::of::<C>()]));
+
+        Ok(MappedSoa3 { map: map, len: len, offsets: offsets, _marker: PhantomData })
+    }
+
+    /// Returns the number of tuples stored in the SoA.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the SoA contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns slices over the SoA's elements.
+    #[inline]
+    pub fn as_slices<'a>(&'a self) -> (&'a [A], &'a [B], &'a [C]) {
+        (column(&self.map, self.offsets[0], self.len),
+         column(&self.map, self.offsets[1], self.len),
+         column(&self.map, self.offsets[2], self.len))
+    }
+
+    /// Copies the mapped elements into a heap allocated `Soa3`.
+    pub fn to_soa(&self) -> Soa3<A, B, C> {
+        let (d0, d1, d2) = self.as_slices();
+        let mut ret = Soa3::new();
+        ret.push_all(d0, d1, d2);
+        ret
+    }
+}
+
+/// A read-only `Soa4` backed by a memory mapped dump.
+pub struct MappedSoa4<A, B, C, D> {
+    map:     Mmap,
+    len:     usize,
+    offsets: Vec<usize>,
+    _marker: PhantomData<(A, B, C, D)>,
+}
+
+impl<A: Pod, B: Pod, C: Pod, D: Pod> MappedSoa4<A, B, C, D> {
+    /// Maps the dump at `path`, checking that its columns are `A`, `B`, `C`,
+    /// and `D`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<MappedSoa4<A, B, C, D>> {
+        let map = try!(self::map(path.as_ref()));
+        let (len, offsets) =
+            try!(parse_header(&map, &[Layout::of::<A>(), Layout::of::<B>(), Layout::of::<C>(), Layout::of::<D>()]));
+
+        Ok(MappedSoa4 { map: map, len: len, offsets: offsets, _marker: PhantomData })
+    }
+
+    /// Returns the number of tuples stored in the SoA.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the SoA contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns slices over the SoA's elements.
+    #[inline]
+    pub fn as_slices<'a>(&'a self) -> (&'a [A], &'a [B], &'a [C], &'a [D]) {
+        (column(&self.map, self.offsets[0], self.len),
+         column(&self.map, self.offsets[1], self.len),
+         column(&self.map, self.offsets[2], self.len),
+         column(&self.map, self.offsets[3], self.len))
+    }
+
+    /// Copies the mapped elements into a heap allocated `Soa4`.
+    pub fn to_soa(&self) -> Soa4<A, B, C, D> {
+        let (d0, d1, d2, d3) = self.as_slices();
+        let mut ret = Soa4::new();
+        ret.push_all(d0, d1, d2, d3);
+        ret
+    }
+}
@@ -0,0 +1,108 @@
+//! Grouping the rows of an SoA by the value of one of its arrays.
+
+use core::hash::Hash;
+use core::ops::Range;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use column::Column;
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+use table::{Row, Table};
+
+/// An iterator over the runs of equal keys in an array, as returned by
+/// `group_by_key`.
+pub struct Groups<'a, K: 'a> {
+    keys:  &'a [K],
+    start: usize,
+}
+
+impl<'a, K: PartialEq> Iterator for Groups<'a, K> {
+    type Item = (&'a K, Range<usize>);
+
+    fn next(&mut self) -> Option<(&'a K, Range<usize>)> {
+        if self.start == self.keys.len() { return None }
+
+        let start = self.start;
+        let key = &self.keys[start];
+        // The first key is always its own group, even if it isn't equal to
+        // itself, like a NaN.
+        let len = 1 + self.keys[start + 1..].iter().take_while(|k| *k == key).count();
+
+        self.start += len;
+        Some((key, start..start + len))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.keys.len() - self.start;
+        (if left == 0 { 0 } else { 1 }, Some(left))
+    }
+}
+
+/// Returns an iterator over the runs of equal elements in `keys`, each with the
+/// range of indices it covers.
+///
+/// On sorted keys, this yields exactly one range per distinct key.
+#[inline]
+pub fn groups<K: PartialEq>(keys: &[K]) -> Groups<K> {
+    Groups { keys: keys, start: 0 }
+}
+
+macro_rules! group {
+    ($soa:ident<$($ty:ident),+>) => {
+        impl<$($ty),+> $soa<$($ty),+> {
+            /// Returns an iterator over the runs of equal elements in the `N`th
+            /// array, each with the range of rows it covers.
+            ///
+            /// The SoA should be sorted by the `N`th array, so that each key
+            /// forms a single run.
+            #[inline]
+            pub fn group_by_key<const N: usize>(&self) -> Groups<<Self as Column<N>>::Elem>
+              where Self: Column<N>, <Self as Column<N>>::Elem: PartialEq {
+                groups(Column::<N>::column(self))
+            }
+
+            /// Folds the rows of each group of equal elements in the `N`th array
+            /// into one row of a new SoA, in order of each key's first
+            /// appearance.
+            ///
+            /// `init` makes the starting aggregate for a key, and `f` folds each
+            /// of that key's rows into it. The rows needn't be sorted.
+            pub fn aggregate_by<const N: usize, R, I, F>(&self, mut init: I, mut f: F) -> R::Table
+              where Self: Column<N>,
+                    <Self as Column<N>>::Elem: Hash + Eq,
+                    R: Row,
+                    I: FnMut(&<Self as Column<N>>::Elem) -> R,
+                    F: FnMut(&mut R, ($(&$ty),+)) {
+                let keys = Column::<N>::column(self);
+                let mut index = HashMap::new();
+                let mut aggs: Vec<R> = Vec::new();
+
+                for (i, key) in keys.iter().enumerate() {
+                    let g =
+                        match index.entry(key) {
+                            Entry::Occupied(o) => *o.get(),
+                            Entry::Vacant(v)   => {
+                                aggs.push(init(key));
+                                *v.insert(aggs.len() - 1)
+                            },
+                        };
+
+                    f(&mut aggs[g], self.get(i).unwrap());
+                }
+
+                let mut ret = R::Table::new();
+                for agg in aggs {
+                    ret.push(agg);
+                }
+                ret
+            }
+        }
+    }
+}
+
+group!(Soa2<A, B>);
+group!(Soa3<A, B, C>);
+group!(Soa4<A, B, C, D>);
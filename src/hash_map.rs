@@ -0,0 +1,343 @@
+use core::borrow::Borrow;
+use core::fmt::{Debug, Formatter, Result};
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::mem;
+use core::slice;
+use core::usize;
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasherDefault;
+
+use table::{Row, Table};
+
+/// Ends a chain of rows whose keys share a hash.
+const NONE: usize = usize::MAX;
+
+/// Passes the hashes computed by a map's `BuildHasher` through unchanged, so
+/// they aren't hashed twice.
+#[derive(Default)]
+struct HashHasher(u64);
+
+impl Hasher for HashHasher {
+    #[inline]
+    fn finish(&self) -> u64 { self.0 }
+
+    #[inline]
+    fn write(&mut self, _: &[u8]) { unreachable!() }
+
+    #[inline]
+    fn write_u64(&mut self, n: u64) { self.0 = n }
+}
+
+/// The hash of a row's key, and the next row whose key has the same hash.
+#[derive(Clone, Copy)]
+struct Link {
+    hash: u64,
+    next: usize,
+}
+
+/// A hash map whose values are stored as columns of an SoA.
+///
+/// `SoaHashMap<K, (A, B)>` keeps its values in a `Soa2<A, B>`, in insertion
+/// order, next to a `Vec` of their keys. Single values are found by key with
+/// `get`, and all of them can be processed in bulk through the columns
+/// returned by `values_as_slices`.
+///
+/// Each key is only stored once. The index maps a key's hash to the first row
+/// with that hash, and rows whose keys collide are chained together, so a
+/// lookup compares against the keys in the rows themselves.
+///
+/// `swap_remove` is O(1) but moves the last entry into the removed one's
+/// place. `shift_remove` preserves the insertion order of the remaining
+/// entries, but is O(n).
+pub struct SoaHashMap<K, V: Row, S = RandomState> {
+    hash_builder: S,
+    heads:        HashMap<u64, usize, BuildHasherDefault<HashHasher>>,
+    links:        Vec<Link>,
+    keys:         Vec<K>,
+    values:       V::Table,
+}
+
+impl<K: Hash + Eq, V: Row> SoaHashMap<K, V> {
+    /// Constructs a new, empty map.
+    pub fn new() -> SoaHashMap<K, V> {
+        SoaHashMap::with_hasher(RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V: Row, S: BuildHasher> SoaHashMap<K, V, S> {
+    /// Constructs a new, empty map which will use `hasher` to hash keys.
+    pub fn with_hasher(hasher: S) -> SoaHashMap<K, V, S> {
+        SoaHashMap {
+            hash_builder: hasher,
+            heads:        HashMap::default(),
+            links:        Vec::new(),
+            keys:         Vec::new(),
+            values:       V::Table::new(),
+        }
+    }
+
+    /// Returns the number of entries in the map.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns `true` if the map contains no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    #[inline]
+    fn hash<Q: ?Sized + Hash>(&self, key: &Q) -> u64 {
+        let mut state = self.hash_builder.build_hasher();
+        key.hash(&mut state);
+        state.finish()
+    }
+
+    /// Returns the row whose key is `key`, given the key's hash.
+    fn find<Q: ?Sized>(&self, hash: u64, key: &Q) -> Option<usize>
+      where K: Borrow<Q>, Q: Eq {
+        let mut row = *self.heads.get(&hash).unwrap_or(&NONE);
+
+        while row != NONE {
+            if self.keys[row].borrow() == key { return Some(row) }
+            row = self.links[row].next;
+        }
+
+        None
+    }
+
+    /// Points whatever refers to `from` in its hash chain at `to` instead.
+    fn relink(&mut self, from: usize, to: usize) {
+        let hash = self.links[from].hash;
+        let head = self.heads.get_mut(&hash).unwrap();
+
+        if *head == from {
+            *head = to;
+            return;
+        }
+
+        let mut row = *head;
+        while self.links[row].next != from {
+            row = self.links[row].next;
+        }
+        self.links[row].next = to;
+    }
+
+    /// Takes `row` out of its hash chain.
+    fn unlink(&mut self, row: usize) {
+        let Link { hash, next } = self.links[row];
+
+        if next == NONE && self.heads[&hash] == row {
+            self.heads.remove(&hash);
+        } else {
+            self.relink(row, next);
+        }
+    }
+
+    /// Returns the position of `key`'s entry in insertion order, which is
+    /// also its row in the value columns.
+    #[inline]
+    pub fn get_index_of<Q: ?Sized>(&self, key: &Q) -> Option<usize>
+      where K: Borrow<Q>, Q: Hash + Eq {
+        self.find(self.hash(key), key)
+    }
+
+    /// Returns `true` if the map has an entry for `key`.
+    #[inline]
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
+      where K: Borrow<Q>, Q: Hash + Eq {
+        self.get_index_of(key).is_some()
+    }
+
+    /// Inserts `value` for `key`.
+    ///
+    /// If `key` was already present its value is replaced in place, keeping
+    /// its position, and the old value is returned. Otherwise the entry is
+    /// added at the end.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash(&key);
+
+        if let Some(index) = self.find(hash, &key) {
+            self.values.push(value);
+            return Some(self.values.swap_remove(index));
+        }
+
+        let row = self.keys.len();
+        let next = self.heads.insert(hash, row).unwrap_or(NONE);
+        self.links.push(Link { hash: hash, next: next });
+        self.keys.push(key);
+        self.values.push(value);
+        None
+    }
+
+    /// Returns the value for `key`, as a tuple of references.
+    #[inline]
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<<V::Table as Table>::Ref<'_>>
+      where K: Borrow<Q>, Q: Hash + Eq {
+        self.get_index_of(key).and_then(move |index| self.values.get(index))
+    }
+
+    /// Returns the value for `key`, as a tuple of mutable references.
+    #[inline]
+    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<<V::Table as Table>::Mut<'_>>
+      where K: Borrow<Q>, Q: Hash + Eq {
+        match self.get_index_of(key) {
+            None        => None,
+            Some(index) => self.values.get_mut(index),
+        }
+    }
+
+    /// Returns the `index`th entry in insertion order.
+    #[inline]
+    pub fn get_index(&self, index: usize) -> Option<(&K, <V::Table as Table>::Ref<'_>)> {
+        match self.keys.get(index) {
+            None    => None,
+            Some(k) => self.values.get(index).map(|v| (k, v)),
+        }
+    }
+
+    /// Removes `key`'s entry and returns its key and value, moving the last
+    /// entry into its place.
+    pub fn swap_remove<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)>
+      where K: Borrow<Q>, Q: Hash + Eq {
+        let index =
+            match self.get_index_of(key) {
+                None        => return None,
+                Some(index) => index,
+            };
+
+        self.unlink(index);
+
+        let last = self.keys.len() - 1;
+        if index != last {
+            self.relink(last, index);
+        }
+
+        self.links.swap_remove(index);
+        let k = self.keys.swap_remove(index);
+        let v = self.values.swap_remove(index);
+        Some((k, v))
+    }
+
+    /// Removes `key`'s entry and returns its key and value, shifting every
+    /// later entry down by one.
+    pub fn shift_remove<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)>
+      where K: Borrow<Q>, Q: Hash + Eq {
+        let index =
+            match self.get_index_of(key) {
+                None        => return None,
+                Some(index) => index,
+            };
+
+        self.unlink(index);
+
+        self.links.remove(index);
+        let k = self.keys.remove(index);
+        let v = self.values.remove(index);
+
+        for row in self.heads.values_mut() {
+            if *row > index { *row -= 1 }
+        }
+        for link in &mut self.links {
+            if link.next != NONE && link.next > index { link.next -= 1 }
+        }
+
+        Some((k, v))
+    }
+
+    /// Removes every entry from the map.
+    pub fn clear(&mut self) {
+        self.heads.clear();
+        self.links.clear();
+        self.keys.clear();
+        self.values.clear();
+    }
+
+    /// Returns the keys, in insertion order.
+    #[inline]
+    pub fn keys(&self) -> &[K] {
+        &self.keys
+    }
+
+    /// Returns the table holding the values.
+    #[inline]
+    pub fn values(&self) -> &V::Table {
+        &self.values
+    }
+
+    /// Returns slices over the value columns, in insertion order.
+    #[inline]
+    pub fn values_as_slices(&self) -> <V::Table as Table>::Slices<'_> {
+        self.values.as_slices()
+    }
+
+    /// Returns mutable slices over the value columns, in insertion order.
+    #[inline]
+    pub fn values_as_mut_slices(&mut self) -> <V::Table as Table>::MutSlices<'_> {
+        self.values.as_mut_slices()
+    }
+
+    /// Returns an iterator over every key and value, in insertion order.
+    #[inline]
+    pub fn iter(&self) -> Iter<K, V> {
+        Iter { keys: self.keys.iter().enumerate(), values: &self.values }
+    }
+
+    /// Consumes the map, returning its keys and the table of its values.
+    pub fn into_parts(self) -> (Vec<K>, V::Table) {
+        (self.keys, self.values)
+    }
+}
+
+impl<K: Hash + Eq, V: Row, S: BuildHasher + Default> Default for SoaHashMap<K, V, S> {
+    fn default() -> SoaHashMap<K, V, S> { SoaHashMap::with_hasher(S::default()) }
+}
+
+impl<K: Hash + Eq, V: Row, S: BuildHasher> Extend<(K, V)> for SoaHashMap<K, V, S> {
+    fn extend<I: IntoIterator<Item=(K, V)>>(&mut self, iterable: I) {
+        for (k, v) in iterable {
+            mem::drop(self.insert(k, v));
+        }
+    }
+}
+
+impl<K: Hash + Eq, V: Row> ::core::iter::FromIterator<(K, V)> for SoaHashMap<K, V> {
+    fn from_iter<I: IntoIterator<Item=(K, V)>>(iterable: I) -> SoaHashMap<K, V> {
+        let mut map = SoaHashMap::new();
+        map.extend(iterable);
+        map
+    }
+}
+
+impl<K: Debug, V: Row, S> Debug for SoaHashMap<K, V, S> where V::Table: Debug {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_struct("SoaHashMap")
+         .field("keys", &self.keys)
+         .field("values", &self.values)
+         .finish()
+    }
+}
+
+/// An iterator over the entries of a `SoaHashMap`, in insertion order.
+pub struct Iter<'a, K: 'a, V: 'a + Row> {
+    keys:   ::core::iter::Enumerate<slice::Iter<'a, K>>,
+    values: &'a V::Table,
+}
+
+impl<'a, K, V: Row> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, <V::Table as Table>::Ref<'a>);
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, <V::Table as Table>::Ref<'a>)> {
+        let values = self.values;
+        self.keys.next().map(|(i, k)| (k, values.get(i).unwrap()))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
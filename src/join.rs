@@ -0,0 +1,259 @@
+//! Joining two SoAs on a key array of each.
+//!
+//! A join pairs up the rows of a left and a right table with equal keys.
+//! `hash_join`, `hash_left_join` and `merge_join` join two keyed `Soa2`s,
+//! whose first array is the key, into one `Soa3` of the key and both sides'
+//! values, storing the key only once.
+//!
+//! For wider tables, or keys in other arrays, the `*_gather` variants return
+//! the result as two tables of equal length, one gathered from each side,
+//! where row `i` of one matches row `i` of the other. Their arrays can then be
+//! combined as needed; two `Soa2`s, for instance, become a `Soa4` with
+//! `Soa2::join`.
+
+use core::cmp::Ordering;
+use core::hash::Hash;
+use core::ptr;
+use std::collections::HashMap;
+
+use column::Column;
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+use table::Table;
+
+/// Tables which can be built by picking out rows of another by index.
+pub trait Gather: Table + Sized {
+    /// Returns a new table of the rows at `indices`, in order. Rows may be
+    /// picked more than once.
+    ///
+    /// Panics if any index is out of bounds.
+    fn gather(&self, indices: &[usize]) -> Self;
+
+    /// Like `gather`, but takes a row of `fill` wherever an index is `None`.
+    fn gather_or(&self, indices: &[Option<usize>], fill: &Self::Row) -> Self;
+}
+
+macro_rules! gather {
+    ($soa:ident<$($ty:ident),+> [$($n:tt $p:ident),+]) => {
+        impl<$($ty: Clone),+> Gather for $soa<$($ty),+> {
+            fn gather(&self, indices: &[usize]) -> $soa<$($ty),+> {
+                let len = self.len();
+                for &i in indices {
+                    assert!(i < len, "gather index out of bounds");
+                }
+
+                let src = self.as_slices();
+                let mut ret = $soa::with_capacity(indices.len());
+
+                unsafe {
+                    let ($($p),+) = ret.as_mut_ptrs();
+
+                    // One array at a time, so each loop only reads from one
+                    // source array.
+                    $(for (j, &i) in indices.iter().enumerate() {
+                        ptr::write($p.offset(j as isize), src.$n.get_unchecked(i).clone());
+                    })+
+
+                    ret.set_len(indices.len());
+                }
+
+                ret
+            }
+
+            fn gather_or(&self, indices: &[Option<usize>], fill: &($($ty),+)) -> $soa<$($ty),+> {
+                let len = self.len();
+                for &i in indices {
+                    assert!(i.map_or(true, |i| i < len), "gather index out of bounds");
+                }
+
+                let src = self.as_slices();
+                let mut ret = $soa::with_capacity(indices.len());
+
+                unsafe {
+                    let ($($p),+) = ret.as_mut_ptrs();
+
+                    $(for (j, &i) in indices.iter().enumerate() {
+                        let x = match i {
+                            Some(i) => src.$n.get_unchecked(i),
+                            None    => &fill.$n,
+                        };
+                        ptr::write($p.offset(j as isize), x.clone());
+                    })+
+
+                    ret.set_len(indices.len());
+                }
+
+                ret
+            }
+        }
+    }
+}
+
+gather!(Soa2<A, B> [0 p0, 1 p1]);
+gather!(Soa3<A, B, C> [0 p0, 1 p1, 2 p2]);
+gather!(Soa4<A, B, C, D> [0 p0, 1 p1, 2 p2, 3 p3]);
+
+/// Returns the pairs of indices of equal keys in `left` and `right`, ordered by
+/// left index, then right index.
+///
+/// This builds a hash table of `right`, so should be given the smaller side
+/// there.
+pub fn hash_join_indices<K: Hash + Eq>(left: &[K], right: &[K]) -> (Vec<usize>, Vec<usize>) {
+    let index = build_index(right);
+    let mut ls = Vec::new();
+    let mut rs = Vec::new();
+
+    for (l, k) in left.iter().enumerate() {
+        if let Some(matches) = index.get(k) {
+            for &r in matches {
+                ls.push(l);
+                rs.push(r);
+            }
+        }
+    }
+
+    (ls, rs)
+}
+
+/// Like `hash_join_indices`, but also keeps the left keys which have no match,
+/// paired with `None`.
+pub fn hash_left_join_indices<K: Hash + Eq>(left: &[K], right: &[K]) -> (Vec<usize>, Vec<Option<usize>>) {
+    let index = build_index(right);
+    let mut ls = Vec::new();
+    let mut rs = Vec::new();
+
+    for (l, k) in left.iter().enumerate() {
+        match index.get(k) {
+            Some(matches) =>
+                for &r in matches {
+                    ls.push(l);
+                    rs.push(Some(r));
+                },
+            None => {
+                ls.push(l);
+                rs.push(None);
+            },
+        }
+    }
+
+    (ls, rs)
+}
+
+fn build_index<K: Hash + Eq>(keys: &[K]) -> HashMap<&K, Vec<usize>> {
+    let mut index: HashMap<&K, Vec<usize>> = HashMap::with_capacity(keys.len());
+    for (i, k) in keys.iter().enumerate() {
+        index.entry(k).or_insert_with(Vec::new).push(i);
+    }
+    index
+}
+
+/// Returns the pairs of indices of equal keys in `left` and `right`, which
+/// must both be sorted, ordered by left index, then right index.
+pub fn merge_join_indices<K: Ord>(left: &[K], right: &[K]) -> (Vec<usize>, Vec<usize>) {
+    let mut ls = Vec::new();
+    let mut rs = Vec::new();
+    let (mut l, mut r) = (0, 0);
+
+    while l < left.len() && r < right.len() {
+        match left[l].cmp(&right[r]) {
+            Ordering::Less    => l += 1,
+            Ordering::Greater => r += 1,
+            Ordering::Equal   => {
+                let k = &left[l];
+                let l_end = l + left[l..].iter().take_while(|x| *x == k).count();
+                let r_end = r + right[r..].iter().take_while(|x| *x == k).count();
+
+                for i in l..l_end {
+                    for j in r..r_end {
+                        ls.push(i);
+                        rs.push(j);
+                    }
+                }
+
+                l = l_end;
+                r = r_end;
+            },
+        }
+    }
+
+    (ls, rs)
+}
+
+/// Inner joins `left` and `right` where the `N`th array of `left` equals the
+/// `M`th array of `right`, using a hash table of `right`'s keys.
+///
+/// Returns the matching rows of each side, in order of left row, then right
+/// row.
+pub fn hash_join_gather<const N: usize, const M: usize, L, R>(left: &L, right: &R) -> (L, R)
+  where L: Column<N> + Gather,
+        R: Column<M, Elem = <L as Column<N>>::Elem> + Gather,
+        <L as Column<N>>::Elem: Hash + Eq {
+    let (ls, rs) = hash_join_indices(Column::<N>::column(left), Column::<M>::column(right));
+    (left.gather(&ls), right.gather(&rs))
+}
+
+/// Left joins `left` and `right` where the `N`th array of `left` equals the
+/// `M`th array of `right`, using a hash table of `right`'s keys.
+///
+/// Like `hash_join_gather`, but left rows with no match are kept too, paired
+/// with a copy of `fill`.
+pub fn hash_left_join_gather<const N: usize, const M: usize, L, R>(left: &L, right: &R, fill: &R::Row) -> (L, R)
+  where L: Column<N> + Gather,
+        R: Column<M, Elem = <L as Column<N>>::Elem> + Gather,
+        <L as Column<N>>::Elem: Hash + Eq {
+    let (ls, rs) = hash_left_join_indices(Column::<N>::column(left), Column::<M>::column(right));
+    (left.gather(&ls), right.gather_or(&rs, fill))
+}
+
+/// Inner joins `left` and `right` where the `N`th array of `left` equals the
+/// `M`th array of `right`, by merging. Both must be sorted by their key array.
+///
+/// Returns the matching rows of each side, in order of left row, then right
+/// row.
+pub fn merge_join_gather<const N: usize, const M: usize, L, R>(left: &L, right: &R) -> (L, R)
+  where L: Column<N> + Gather,
+        R: Column<M, Elem = <L as Column<N>>::Elem> + Gather,
+        <L as Column<N>>::Elem: Ord {
+    let (ls, rs) = merge_join_indices(Column::<N>::column(left), Column::<M>::column(right));
+    (left.gather(&ls), right.gather(&rs))
+}
+
+/// Inner joins two keyed SoAs on their first arrays, using a hash table of
+/// `right`'s keys.
+///
+/// Returns a row of the key, the left value and the right value for each
+/// match, in order of left row, then right row.
+pub fn hash_join<K, B, D>(left: &Soa2<K, B>, right: &Soa2<K, D>) -> Soa3<K, B, D>
+  where K: Hash + Eq + Clone, B: Clone, D: Clone {
+    let (ls, rs) = hash_join_indices(left.as_slices().0, right.as_slices().0);
+    left.gather(&ls).with_column(gather_column(right.as_slices().1, &rs))
+}
+
+/// Left joins two keyed SoAs on their first arrays, using a hash table of
+/// `right`'s keys.
+///
+/// Like `hash_join`, but left rows with no match are kept too, with a copy of
+/// `fill` as their right value.
+pub fn hash_left_join<K, B, D>(left: &Soa2<K, B>, right: &Soa2<K, D>, fill: &D) -> Soa3<K, B, D>
+  where K: Hash + Eq + Clone, B: Clone, D: Clone {
+    let (ls, rs) = hash_left_join_indices(left.as_slices().0, right.as_slices().0);
+    let d = right.as_slices().1;
+    let column = rs.iter().map(|&r| r.map_or(fill, |r| &d[r]).clone()).collect();
+    left.gather(&ls).with_column(column)
+}
+
+/// Inner joins two keyed SoAs on their first arrays, by merging. Both must be
+/// sorted by key.
+///
+/// Returns a row of the key, the left value and the right value for each
+/// match, in order of left row, then right row.
+pub fn merge_join<K, B, D>(left: &Soa2<K, B>, right: &Soa2<K, D>) -> Soa3<K, B, D>
+  where K: Ord + Clone, B: Clone, D: Clone {
+    let (ls, rs) = merge_join_indices(left.as_slices().0, right.as_slices().0);
+    left.gather(&ls).with_column(gather_column(right.as_slices().1, &rs))
+}
+
+fn gather_column<T: Clone>(src: &[T], indices: &[usize]) -> Vec<T> {
+    indices.iter().map(|&i| src[i].clone()).collect()
+}
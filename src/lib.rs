@@ -10,13 +10,56 @@ extern crate alloc;
 extern crate collections;
 extern crate core;
 
+#[cfg(feature = "rayon")] extern crate rayon;
+#[cfg(feature = "serde")] #[macro_use] extern crate serde;
+#[cfg(all(test, feature = "serde"))] extern crate serde_json;
+#[cfg(feature = "arrow")] extern crate arrow;
+#[cfg(feature = "bytemuck")] extern crate bytemuck;
+#[cfg(feature = "memmap2")] extern crate memmap2;
+
 pub mod soa2;
 pub mod soa3;
 pub mod soa4;
+pub mod array_soa2;
+pub mod small_soa2;
+pub mod soa_deque2;
+pub mod chunked_soa2;
+pub mod append_soa2;
+pub mod cow_soa;
+pub mod column;
+pub mod soa_slice;
+pub mod table;
+pub mod slot_map;
+pub mod sparse_set;
+pub mod hash_map;
+pub mod group;
+pub mod join;
+pub mod mask;
+pub mod nullable;
+pub mod bits;
+
+#[cfg(feature = "serde")] pub mod serialize;
+#[cfg(feature = "bytemuck")] pub mod bytes;
+#[cfg(feature = "mmap")] pub mod dump;
+#[cfg(feature = "arrow")] pub mod arrow_interop;
+#[cfg(feature = "stats")] pub mod stats;
 
 mod unadorned;
+#[cfg(feature = "rayon")] mod par;
 #[cfg(test)] mod test;
 
 pub use soa2::Soa2;
 pub use soa3::Soa3;
 pub use soa4::Soa4;
+pub use array_soa2::ArraySoa2;
+pub use small_soa2::SmallSoa2;
+pub use soa_deque2::SoaDeque2;
+pub use chunked_soa2::ChunkedSoa2;
+pub use append_soa2::AppendSoa2;
+pub use cow_soa::{CowSoa2, CowSoa3, CowSoa4};
+pub use column::{Column, ColumnMut, DropColumn, MapColumn};
+pub use soa_slice::{Soa2Slice, Soa2SliceMut};
+pub use table::Table;
+pub use slot_map::SoaSlotMap;
+pub use sparse_set::SparseSet;
+pub use hash_map::SoaHashMap;
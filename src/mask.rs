@@ -0,0 +1,372 @@
+//! Selecting rows of an SoA with a mask, without touching the SoA itself.
+//!
+//! A predicate over one array is turned into a `Bitmask` by `compute_mask`.
+//! Masks can be combined with bitwise logic, then used to iterate over the
+//! selected rows in place with `select`, or to copy them out into a new SoA
+//! with `filter_mask`. A plain `[bool]` works as a mask too.
+
+use core::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+use core::ops::Range;
+
+use column::Column;
+use join::Gather;
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+use table::Table;
+
+/// Something which selects some of the indices `0..len()`.
+pub trait Mask {
+    /// Returns the number of indices the mask covers.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if index `i` is selected.
+    ///
+    /// Panics if `i` is out of bounds.
+    fn test(&self, i: usize) -> bool;
+
+    /// Returns the selected indices, in order.
+    fn indices(&self) -> Vec<usize> {
+        (0..self.len()).filter(|&i| self.test(i)).collect()
+    }
+}
+
+impl Mask for [bool] {
+    #[inline]
+    fn len(&self) -> usize { <[bool]>::len(self) }
+
+    #[inline]
+    fn test(&self, i: usize) -> bool { self[i] }
+}
+
+impl Mask for Vec<bool> {
+    #[inline]
+    fn len(&self) -> usize { Vec::len(self) }
+
+    #[inline]
+    fn test(&self, i: usize) -> bool { self[i] }
+}
+
+const WORD_BITS: usize = 64;
+
+/// A mask stored as one bit per index, packed into `u64`s.
+///
+/// Bits past `len()` in the last word are always zero.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Bitmask {
+    words: Vec<u64>,
+    len:   usize,
+}
+
+impl Bitmask {
+    /// Constructs a mask of `len` indices, none of them selected.
+    pub fn new(len: usize) -> Bitmask {
+        Bitmask { words: vec![0; (len + WORD_BITS - 1) / WORD_BITS], len: len }
+    }
+
+    /// Constructs a mask selecting the indices of the `true`s in `bools`.
+    pub fn from_bools(bools: &[bool]) -> Bitmask {
+        Bitmask::from_fn(bools, |&b| b)
+    }
+
+    /// Constructs a mask selecting the indices of the elements of `xs` for
+    /// which `f` returns `true`.
+    ///
+    /// Each word is filled in by shifting 64 results into place with no
+    /// branches, which the compiler can vectorize.
+    pub fn from_fn<T, F: FnMut(&T) -> bool>(xs: &[T], mut f: F) -> Bitmask {
+        let mut words = Vec::with_capacity((xs.len() + WORD_BITS - 1) / WORD_BITS);
+
+        for chunk in xs.chunks(WORD_BITS) {
+            let mut word = 0;
+            for (j, x) in chunk.iter().enumerate() {
+                word |= (f(x) as u64) << j;
+            }
+            words.push(word);
+        }
+
+        Bitmask { words: words, len: xs.len() }
+    }
+
+    /// Returns the number of indices the mask covers.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the mask covers no indices.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if index `i` is selected.
+    ///
+    /// Panics if `i` is out of bounds.
+    #[inline]
+    pub fn get(&self, i: usize) -> bool {
+        assert!(i < self.len, "index out of bounds");
+        self.words[i / WORD_BITS] & (1 << (i % WORD_BITS)) != 0
+    }
+
+    /// Selects or deselects index `i`.
+    ///
+    /// Panics if `i` is out of bounds.
+    #[inline]
+    pub fn set(&mut self, i: usize, value: bool) {
+        assert!(i < self.len, "index out of bounds");
+        let bit = 1 << (i % WORD_BITS);
+        if value {
+            self.words[i / WORD_BITS] |= bit;
+        } else {
+            self.words[i / WORD_BITS] &= !bit;
+        }
+    }
+
+    /// Appends an index to the end of the mask, selected if `value` is `true`.
+    #[inline]
+    pub fn push(&mut self, value: bool) {
+        if self.len % WORD_BITS == 0 {
+            self.words.push(0);
+        }
+        self.len += 1;
+        let i = self.len - 1;
+        self.set(i, value);
+    }
+
+    /// Removes the last index from the mask and returns whether it was
+    /// selected, or `None` if the mask is empty.
+    #[inline]
+    pub fn pop(&mut self) -> Option<bool> {
+        if self.len == 0 { return None }
+
+        let ret = self.get(self.len - 1);
+        self.truncate(self.len - 1);
+        Some(ret)
+    }
+
+    /// Removes index `i`, replacing it with the last index, and returns
+    /// whether it was selected.
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn swap_remove(&mut self, i: usize) -> bool {
+        let ret = self.get(i);
+        let last = self.pop().unwrap();
+        if i < self.len {
+            self.set(i, last);
+        }
+        ret
+    }
+
+    /// Shortens the mask to `len` indices. Has no effect if it is already
+    /// shorter.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len { return }
+
+        self.words.truncate((len + WORD_BITS - 1) / WORD_BITS);
+        if len % WORD_BITS != 0 {
+            let last = self.words.len() - 1;
+            self.words[last] &= (1 << (len % WORD_BITS)) - 1;
+        }
+        self.len = len;
+    }
+
+    /// Returns the words the bits are packed into. Index `i` is bit
+    /// `i % 64` of word `i / 64`.
+    #[inline]
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Returns the number of selected indices.
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).fold(0, |a, b| a + b)
+    }
+
+    /// Returns an iterator over the selected indices, in order.
+    #[inline]
+    pub fn iter_ones(&self) -> Ones {
+        Ones { words: &self.words, word: 0, bits: self.words.first().cloned().unwrap_or(0) }
+    }
+
+    /// Flips every index, a word at a time.
+    pub fn negate(&mut self) {
+        for w in &mut self.words {
+            *w = !*w;
+        }
+        if self.len % WORD_BITS != 0 {
+            let last = self.words.len() - 1;
+            self.words[last] &= (1 << (self.len % WORD_BITS)) - 1;
+        }
+    }
+
+    fn zip_with_assign<F: Fn(u64, u64) -> u64>(&mut self, other: &Bitmask, f: F) {
+        assert_eq!(self.len, other.len);
+        for (a, &b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a = f(*a, b);
+        }
+    }
+
+    fn zip_with<F: Fn(u64, u64) -> u64>(&self, other: &Bitmask, f: F) -> Bitmask {
+        assert_eq!(self.len, other.len);
+        Bitmask {
+            words: self.words.iter().zip(other.words.iter()).map(|(&a, &b)| f(a, b)).collect(),
+            len:   self.len,
+        }
+    }
+}
+
+impl Mask for Bitmask {
+    #[inline]
+    fn len(&self) -> usize { self.len }
+
+    #[inline]
+    fn test(&self, i: usize) -> bool { self.get(i) }
+
+    fn indices(&self) -> Vec<usize> { self.iter_ones().collect() }
+}
+
+impl<'a> BitAnd for &'a Bitmask {
+    type Output = Bitmask;
+
+    /// Selects the indices selected by both masks.
+    ///
+    /// Panics if the masks are of different lengths.
+    fn bitand(self, other: &Bitmask) -> Bitmask { self.zip_with(other, |a, b| a & b) }
+}
+
+impl<'a> BitOr for &'a Bitmask {
+    type Output = Bitmask;
+
+    /// Selects the indices selected by either mask.
+    ///
+    /// Panics if the masks are of different lengths.
+    fn bitor(self, other: &Bitmask) -> Bitmask { self.zip_with(other, |a, b| a | b) }
+}
+
+impl<'a> Not for &'a Bitmask {
+    type Output = Bitmask;
+
+    /// Selects the indices not selected by the mask.
+    fn not(self) -> Bitmask {
+        let mut ret = self.clone();
+        ret.negate();
+        ret
+    }
+}
+
+impl<'a> BitXor for &'a Bitmask {
+    type Output = Bitmask;
+
+    /// Selects the indices selected by exactly one of the masks.
+    ///
+    /// Panics if the masks are of different lengths.
+    fn bitxor(self, other: &Bitmask) -> Bitmask { self.zip_with(other, |a, b| a ^ b) }
+}
+
+impl<'a> BitAndAssign<&'a Bitmask> for Bitmask {
+    /// Deselects the indices not selected by `other`, a word at a time.
+    ///
+    /// Panics if the masks are of different lengths.
+    fn bitand_assign(&mut self, other: &Bitmask) { self.zip_with_assign(other, |a, b| a & b) }
+}
+
+impl<'a> BitOrAssign<&'a Bitmask> for Bitmask {
+    /// Selects the indices selected by `other` too, a word at a time.
+    ///
+    /// Panics if the masks are of different lengths.
+    fn bitor_assign(&mut self, other: &Bitmask) { self.zip_with_assign(other, |a, b| a | b) }
+}
+
+impl<'a> BitXorAssign<&'a Bitmask> for Bitmask {
+    /// Flips the indices selected by `other`, a word at a time.
+    ///
+    /// Panics if the masks are of different lengths.
+    fn bitxor_assign(&mut self, other: &Bitmask) { self.zip_with_assign(other, |a, b| a ^ b) }
+}
+
+/// An iterator over the selected indices of a `Bitmask`.
+pub struct Ones<'a> {
+    words: &'a [u64],
+    word:  usize,
+    bits:  u64,
+}
+
+impl<'a> Iterator for Ones<'a> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        while self.bits == 0 {
+            self.word += 1;
+            if self.word >= self.words.len() { return None }
+            self.bits = self.words[self.word];
+        }
+
+        let bit = self.bits.trailing_zeros() as usize;
+        self.bits &= self.bits - 1;
+        Some(self.word * WORD_BITS + bit)
+    }
+}
+
+/// An iterator over the rows of a table selected by a mask, as returned by
+/// `select`.
+pub struct Select<'a, T: 'a + Table, M: 'a + ?Sized + Mask> {
+    table: &'a T,
+    mask:  &'a M,
+    rows:  Range<usize>,
+}
+
+impl<'a, T: Table, M: ?Sized + Mask> Iterator for Select<'a, T, M> {
+    type Item = (usize, T::Ref<'a>);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, T::Ref<'a>)> {
+        let (table, mask) = (self.table, self.mask);
+        self.rows.by_ref().find(|&i| mask.test(i)).map(|i| (i, table.get(i).unwrap()))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.rows.size_hint().1)
+    }
+}
+
+macro_rules! mask {
+    ($soa:ident<$($ty:ident),+>) => {
+        impl<$($ty),+> $soa<$($ty),+> {
+            /// Returns a mask selecting the rows whose element of the `N`th
+            /// array satisfies `f`.
+            #[inline]
+            pub fn compute_mask<const N: usize, F>(&self, f: F) -> Bitmask
+              where Self: Column<N>, F: FnMut(&<Self as Column<N>>::Elem) -> bool {
+                Bitmask::from_fn(Column::<N>::column(self), f)
+            }
+
+            /// Returns an iterator over the rows selected by `mask`, along with
+            /// their indices. Nothing is copied.
+            ///
+            /// Panics if `mask` isn't the same length as the SoA.
+            #[inline]
+            pub fn select<'a, M: ?Sized + Mask>(&'a self, mask: &'a M) -> Select<'a, Self, M> {
+                assert_eq!(mask.len(), self.len());
+                Select { table: self, mask: mask, rows: 0..self.len() }
+            }
+        }
+
+        impl<$($ty: Clone),+> $soa<$($ty),+> {
+            /// Returns a new SoA of clones of the rows selected by `mask`, in
+            /// order.
+            ///
+            /// Panics if `mask` isn't the same length as the SoA.
+            pub fn filter_mask<M: ?Sized + Mask>(&self, mask: &M) -> $soa<$($ty),+> {
+                assert_eq!(mask.len(), self.len());
+                self.gather(&mask.indices())
+            }
+        }
+    }
+}
+
+mask!(Soa2<A, B>);
+mask!(Soa3<A, B, C>);
+mask!(Soa4<A, B, C, D>);
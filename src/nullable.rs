@@ -0,0 +1,263 @@
+//! Arrays with missing values, stored densely alongside a validity bitmap.
+//!
+//! An array of `Option<u32>` spends 8 bytes a row, half of it padding. Instead,
+//! `NullableSoa<Soa3<f32, Nullable<u32>, f64>, 1>` keeps its second array as
+//! plain `u32`s, and records which rows hold a value in a `Bitmask`, one bit a
+//! row, like Arrow does. A null row's slot in the array holds a placeholder,
+//! which `get` and `iter` hide by returning `None`.
+//!
+//! # Limitations
+//!
+//! Only one array of a `NullableSoa` can be nullable: the `N`th, whose
+//! validity bitmap is the single bit array of the `BitSoa` underneath (see
+//! the `bits` module for why the bits can't live in the SoA itself). A table
+//! with several arrays of missing values needs an `Option` or a sentinel in
+//! all but one of them.
+//!
+//! `Nullable<T>` is an ordinary element type to the SoA, so the SoA's own
+//! accessors know nothing of the bitmap: `Soa3<f32, Nullable<u32>, f64>::get`
+//! still returns `&Nullable<u32>`, placeholder and all. Only the
+//! `NullableSoa`'s `get` and `iter` return `Option<&u32>`.
+
+use core::fmt::{Debug, Formatter, Result};
+
+use bits::BitSoa;
+use column::{Column, ColumnMut};
+use mask::Bitmask;
+use table::Table;
+
+/// An element of an array which may have missing values.
+///
+/// This is laid out exactly like a `T`, so an array of them is as dense as an
+/// array of `T`s. Whether a value is present is kept by the `NullableSoa`.
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Nullable<T>(pub T);
+
+impl<T> Nullable<T> {
+    /// Returns the wrapped value, whether or not its row is null.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: Default> Nullable<T> {
+    /// Returns a placeholder for a null row.
+    #[inline]
+    pub fn null() -> Nullable<T> {
+        Nullable(T::default())
+    }
+}
+
+/// An SoA whose `N`th array holds `Nullable` elements, with a validity bitmap
+/// recording which of them are present. No other array may be nullable.
+///
+/// This is a `BitSoa` whose bits are the validity bitmap. Element access goes
+/// through the `N`th array, as `Option`s. Whole rows are only handed out by
+/// value, by `pop` and `swap_remove`, or through `soa()`, and hold a
+/// placeholder wherever the `N`th element is null.
+pub struct NullableSoa<S: Table, const N: usize> {
+    inner: BitSoa<S>,
+}
+
+impl<S: Table, T, const N: usize> NullableSoa<S, N>
+  where S: Column<N, Elem = Nullable<T>> {
+    /// Constructs a new, empty SoA.
+    pub fn new() -> NullableSoa<S, N> {
+        NullableSoa { inner: BitSoa::new() }
+    }
+
+    /// Wraps an SoA, with every row of the `N`th array present.
+    pub fn from_soa(soa: S) -> NullableSoa<S, N> {
+        let mut validity = Bitmask::new(soa.len());
+        validity.negate();
+        NullableSoa { inner: BitSoa::from_parts(soa, validity) }
+    }
+
+    /// Returns the number of rows in the SoA.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Returns `true` if the SoA contains no rows.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Returns the number of null rows.
+    #[inline]
+    pub fn null_count(&self) -> usize {
+        self.len() - self.validity().count_ones()
+    }
+
+    /// Returns the validity bitmap: bit `i` is set if row `i` is present.
+    #[inline]
+    pub fn validity(&self) -> &Bitmask {
+        self.inner.bits()
+    }
+
+    /// Returns the underlying SoA. Null rows hold placeholders.
+    #[inline]
+    pub fn soa(&self) -> &S {
+        self.inner.soa()
+    }
+
+    /// Appends a row, whose `N`th element is present.
+    #[inline]
+    pub fn push(&mut self, row: S::Row) {
+        self.inner.push(row, true);
+    }
+
+    /// Appends a row whose `N`th element is null. Its value in `row` is kept
+    /// as a placeholder; `Nullable::null()` makes a suitable one.
+    #[inline]
+    pub fn push_null(&mut self, row: S::Row) {
+        self.inner.push(row, false);
+    }
+
+    /// Removes the last row and returns it along with whether its `N`th
+    /// element was present, or `None` if the SoA is empty.
+    #[inline]
+    pub fn pop(&mut self) -> Option<(S::Row, bool)> {
+        self.inner.pop()
+    }
+
+    /// Removes the `index`th row and returns it along with whether its `N`th
+    /// element was present, replacing it with the last row.
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn swap_remove(&mut self, index: usize) -> (S::Row, bool) {
+        self.inner.swap_remove(index)
+    }
+
+    /// Shortens the SoA, keeping the first `len` rows.
+    #[inline]
+    pub fn truncate(&mut self, len: usize) {
+        self.inner.truncate(len)
+    }
+
+    /// Keeps only the rows for which `f` returns `true`, preserving their
+    /// order. `f` is passed each row along with whether its `N`th element is
+    /// present.
+    ///
+    /// Every row is passed to `f` before any is removed, so if `f` panics the
+    /// SoA is left untouched.
+    #[inline]
+    pub fn retain<F>(&mut self, f: F) where F: for<'a> FnMut(S::Ref<'a>, bool) -> bool {
+        self.inner.retain(f)
+    }
+
+    /// Removes every row.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    /// Returns `true` if the `N`th element of the `index`th row is present.
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn is_valid(&self, index: usize) -> bool {
+        self.inner.bit(index)
+    }
+
+    /// Marks the `N`th element of the `index`th row as present or null.
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn set_valid(&mut self, index: usize, valid: bool) {
+        self.inner.set_bit(index, valid)
+    }
+
+    /// Returns the `N`th element of the `index`th row, which is `None` if it
+    /// is null, or `None` if `index` is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<Option<&T>> {
+        self.inner.column::<N>().get(index).map(|x| {
+            if self.inner.bit(index) { Some(&x.0) } else { None }
+        })
+    }
+
+    /// Sets the `N`th element of the `index`th row, marking it present if
+    /// `value` is `Some` and null otherwise.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn set_nullable(&mut self, index: usize, value: Option<T>)
+      where S: ColumnMut<N>, T: Default {
+        self.inner.set_bit(index, value.is_some());
+        self.inner.column_mut::<N>()[index] = Nullable(value.unwrap_or_default());
+    }
+
+    /// Returns the `N`th array, as dense values and the bitmap of which are
+    /// present.
+    #[inline]
+    pub fn nullable_column(&self) -> (&[T], &Bitmask) {
+        let xs = self.inner.column::<N>();
+        // `Nullable<T>` is a transparent wrapper around `T`
+        let xs = unsafe { ::core::slice::from_raw_parts(xs.as_ptr() as *const T, xs.len()) };
+        (xs, self.validity())
+    }
+
+    /// Returns an iterator over the `N`th array, as `Option`s.
+    #[inline]
+    pub fn iter(&self) -> IterNullable<T> {
+        let (xs, validity) = self.nullable_column();
+        IterNullable { xs: xs, validity: validity, i: 0 }
+    }
+
+    /// Returns slices over the SoA's arrays. Null rows hold placeholders.
+    #[inline]
+    pub fn as_slices(&self) -> S::Slices<'_> {
+        self.inner.as_slices()
+    }
+
+    /// Consumes the SoA, returning the underlying SoA and validity bitmap.
+    #[inline]
+    pub fn into_parts(self) -> (S, Bitmask) {
+        self.inner.into_parts()
+    }
+}
+
+impl<S: Table, T, const N: usize> Default for NullableSoa<S, N>
+  where S: Column<N, Elem = Nullable<T>> {
+    fn default() -> NullableSoa<S, N> { NullableSoa::new() }
+}
+
+impl<S: Table + Debug, const N: usize> Debug for NullableSoa<S, N> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_struct("NullableSoa")
+         .field("soa", self.inner.soa())
+         .field("validity", self.inner.bits())
+         .finish()
+    }
+}
+
+/// An iterator over a nullable array, as `Option`s.
+pub struct IterNullable<'a, T: 'a> {
+    xs:       &'a [T],
+    validity: &'a Bitmask,
+    i:        usize,
+}
+
+impl<'a, T> Iterator for IterNullable<'a, T> {
+    type Item = Option<&'a T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Option<&'a T>> {
+        if self.i == self.xs.len() { return None }
+
+        let i = self.i;
+        self.i += 1;
+        Some(if self.validity.get(i) { Some(&self.xs[i]) } else { None })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.xs.len() - self.i;
+        (left, Some(left))
+    }
+}
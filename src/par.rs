@@ -0,0 +1,166 @@
+//! Parallel iteration over SoA types, powered by `rayon`.
+//!
+//! All the iterators here are built by zipping together one indexed parallel
+//! iterator per array, so every array is split at the same row indices.
+
+use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelExtend};
+use rayon::iter::{Map, Zip};
+use rayon::prelude::*;
+use rayon::slice::{ChunksMut, Iter, IterMut};
+
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+
+impl<A: Sync, B: Sync> Soa2<A, B> {
+    /// Returns a parallel iterator over the SoA's elements, zipped up.
+    #[inline]
+    pub fn par_iter(&self) -> Zip<Iter<A>, Iter<B>> {
+        let (d0, d1) = self.as_slices();
+        d0.par_iter().zip(d1.par_iter())
+    }
+}
+
+impl<A: Send, B: Send> Soa2<A, B> {
+    /// Returns a parallel iterator over the SoA's mutable elements, zipped up.
+    #[inline]
+    pub fn par_iter_mut(&mut self) -> Zip<IterMut<A>, IterMut<B>> {
+        let (d0, d1) = self.as_mut_slices();
+        d0.par_iter_mut().zip(d1.par_iter_mut())
+    }
+
+    /// Returns a parallel iterator over `chunk_size` rows at a time, as
+    /// mutable slices of each array.
+    ///
+    /// The last chunk may be shorter than `chunk_size`. Panics if `chunk_size`
+    /// is 0.
+    #[inline]
+    pub fn par_chunks_mut(&mut self, chunk_size: usize) -> Zip<ChunksMut<A>, ChunksMut<B>> {
+        let (d0, d1) = self.as_mut_slices();
+        d0.par_chunks_mut(chunk_size).zip(d1.par_chunks_mut(chunk_size))
+    }
+}
+
+impl<A: Send, B: Send> ParallelExtend<(A, B)> for Soa2<A, B> {
+    fn par_extend<I>(&mut self, par_iter: I) where I: IntoParallelIterator<Item=(A, B)> {
+        let (v0, v1): (Vec<A>, Vec<B>) = par_iter.into_par_iter().unzip();
+        self.extend(v0.into_iter(), v1.into_iter());
+    }
+}
+
+impl<A: Send, B: Send> FromParallelIterator<(A, B)> for Soa2<A, B> {
+    fn from_par_iter<I>(par_iter: I) -> Soa2<A, B> where I: IntoParallelIterator<Item=(A, B)> {
+        let mut v = Soa2::new();
+        v.par_extend(par_iter);
+        v
+    }
+}
+
+impl<A: Sync, B: Sync, C: Sync> Soa3<A, B, C> {
+    /// Returns a parallel iterator over the SoA's elements, zipped up.
+    #[inline]
+    pub fn par_iter<'a>(&'a self) -> Map<Zip<Zip<Iter<'a, A>, Iter<'a, B>>, Iter<'a, C>>, fn(((&'a A, &'a B), &'a C)) -> (&'a A, &'a B, &'a C)> {
+        let (d0, d1, d2) = self.as_slices();
+        fn repack<A, B, C>(((x, y), z): ((A, B), C)) -> (A, B, C) { (x, y, z) }
+        let repack: fn(((&'a A, &'a B), &'a C)) -> (&'a A, &'a B, &'a C) = repack;
+        d0.par_iter().zip(d1.par_iter()).zip(d2.par_iter()).map(repack)
+    }
+}
+
+impl<A: Send, B: Send, C: Send> Soa3<A, B, C> {
+    /// Returns a parallel iterator over the SoA's mutable elements, zipped up.
+    #[inline]
+    pub fn par_iter_mut<'a>(&'a mut self) -> Map<Zip<Zip<IterMut<'a, A>, IterMut<'a, B>>, IterMut<'a, C>>, fn(((&'a mut A, &'a mut B), &'a mut C)) -> (&'a mut A, &'a mut B, &'a mut C)> {
+        let (d0, d1, d2) = self.as_mut_slices();
+        fn repack<A, B, C>(((x, y), z): ((A, B), C)) -> (A, B, C) { (x, y, z) }
+        let repack: fn(((&'a mut A, &'a mut B), &'a mut C)) -> (&'a mut A, &'a mut B, &'a mut C) = repack;
+        d0.par_iter_mut().zip(d1.par_iter_mut()).zip(d2.par_iter_mut()).map(repack)
+    }
+
+    /// Returns a parallel iterator over `chunk_size` rows at a time, as
+    /// mutable slices of each array.
+    ///
+    /// The last chunk may be shorter than `chunk_size`. Panics if `chunk_size`
+    /// is 0.
+    #[inline]
+    pub fn par_chunks_mut<'a>(&'a mut self, chunk_size: usize) -> Map<Zip<Zip<ChunksMut<'a, A>, ChunksMut<'a, B>>, ChunksMut<'a, C>>, fn(((&'a mut [A], &'a mut [B]), &'a mut [C])) -> (&'a mut [A], &'a mut [B], &'a mut [C])> {
+        let (d0, d1, d2) = self.as_mut_slices();
+        fn repack<A, B, C>(((x, y), z): ((A, B), C)) -> (A, B, C) { (x, y, z) }
+        let repack: fn(((&'a mut [A], &'a mut [B]), &'a mut [C])) -> (&'a mut [A], &'a mut [B], &'a mut [C]) = repack;
+        d0.par_chunks_mut(chunk_size)
+          .zip(d1.par_chunks_mut(chunk_size))
+          .zip(d2.par_chunks_mut(chunk_size))
+          .map(repack)
+    }
+}
+
+impl<A: Send, B: Send, C: Send> ParallelExtend<(A, B, C)> for Soa3<A, B, C> {
+    fn par_extend<I>(&mut self, par_iter: I) where I: IntoParallelIterator<Item=(A, B, C)> {
+        let (v0, (v1, v2)): (Vec<A>, (Vec<B>, Vec<C>)) =
+            par_iter.into_par_iter().map(|(x, y, z)| (x, (y, z))).unzip();
+        self.extend(v0.into_iter(), v1.into_iter(), v2.into_iter());
+    }
+}
+
+impl<A: Send, B: Send, C: Send> FromParallelIterator<(A, B, C)> for Soa3<A, B, C> {
+    fn from_par_iter<I>(par_iter: I) -> Soa3<A, B, C> where I: IntoParallelIterator<Item=(A, B, C)> {
+        let mut v = Soa3::new();
+        v.par_extend(par_iter);
+        v
+    }
+}
+
+impl<A: Sync, B: Sync, C: Sync, D: Sync> Soa4<A, B, C, D> {
+    /// Returns a parallel iterator over the SoA's elements, zipped up.
+    #[inline]
+    pub fn par_iter<'a>(&'a self) -> Map<Zip<Zip<Zip<Iter<'a, A>, Iter<'a, B>>, Iter<'a, C>>, Iter<'a, D>>, fn((((&'a A, &'a B), &'a C), &'a D)) -> (&'a A, &'a B, &'a C, &'a D)> {
+        let (d0, d1, d2, d3) = self.as_slices();
+        fn repack<A, B, C, D>((((w, x), y), z): (((A, B), C), D)) -> (A, B, C, D) { (w, x, y, z) }
+        let repack: fn((((&'a A, &'a B), &'a C), &'a D)) -> (&'a A, &'a B, &'a C, &'a D) = repack;
+        d0.par_iter().zip(d1.par_iter()).zip(d2.par_iter()).zip(d3.par_iter()).map(repack)
+    }
+}
+
+impl<A: Send, B: Send, C: Send, D: Send> Soa4<A, B, C, D> {
+    /// Returns a parallel iterator over the SoA's mutable elements, zipped up.
+    #[inline]
+    pub fn par_iter_mut<'a>(&'a mut self) -> Map<Zip<Zip<Zip<IterMut<'a, A>, IterMut<'a, B>>, IterMut<'a, C>>, IterMut<'a, D>>, fn((((&'a mut A, &'a mut B), &'a mut C), &'a mut D)) -> (&'a mut A, &'a mut B, &'a mut C, &'a mut D)> {
+        let (d0, d1, d2, d3) = self.as_mut_slices();
+        fn repack<A, B, C, D>((((w, x), y), z): (((A, B), C), D)) -> (A, B, C, D) { (w, x, y, z) }
+        let repack: fn((((&'a mut A, &'a mut B), &'a mut C), &'a mut D)) -> (&'a mut A, &'a mut B, &'a mut C, &'a mut D) = repack;
+        d0.par_iter_mut().zip(d1.par_iter_mut()).zip(d2.par_iter_mut()).zip(d3.par_iter_mut()).map(repack)
+    }
+
+    /// Returns a parallel iterator over `chunk_size` rows at a time, as
+    /// mutable slices of each array.
+    ///
+    /// The last chunk may be shorter than `chunk_size`. Panics if `chunk_size`
+    /// is 0.
+    #[inline]
+    pub fn par_chunks_mut<'a>(&'a mut self, chunk_size: usize) -> Map<Zip<Zip<Zip<ChunksMut<'a, A>, ChunksMut<'a, B>>, ChunksMut<'a, C>>, ChunksMut<'a, D>>, fn((((&'a mut [A], &'a mut [B]), &'a mut [C]), &'a mut [D])) -> (&'a mut [A], &'a mut [B], &'a mut [C], &'a mut [D])> {
+        let (d0, d1, d2, d3) = self.as_mut_slices();
+        fn repack<A, B, C, D>((((w, x), y), z): (((A, B), C), D)) -> (A, B, C, D) { (w, x, y, z) }
+        let repack: fn((((&'a mut [A], &'a mut [B]), &'a mut [C]), &'a mut [D])) -> (&'a mut [A], &'a mut [B], &'a mut [C], &'a mut [D]) = repack;
+        d0.par_chunks_mut(chunk_size)
+          .zip(d1.par_chunks_mut(chunk_size))
+          .zip(d2.par_chunks_mut(chunk_size))
+          .zip(d3.par_chunks_mut(chunk_size))
+          .map(repack)
+    }
+}
+
+impl<A: Send, B: Send, C: Send, D: Send> ParallelExtend<(A, B, C, D)> for Soa4<A, B, C, D> {
+    fn par_extend<I>(&mut self, par_iter: I) where I: IntoParallelIterator<Item=(A, B, C, D)> {
+        let (v0, (v1, (v2, v3))): (Vec<A>, (Vec<B>, (Vec<C>, Vec<D>))) =
+            par_iter.into_par_iter().map(|(w, x, y, z)| (w, (x, (y, z)))).unzip();
+        self.extend(v0.into_iter(), v1.into_iter(), v2.into_iter(), v3.into_iter());
+    }
+}
+
+impl<A: Send, B: Send, C: Send, D: Send> FromParallelIterator<(A, B, C, D)> for Soa4<A, B, C, D> {
+    fn from_par_iter<I>(par_iter: I) -> Soa4<A, B, C, D> where I: IntoParallelIterator<Item=(A, B, C, D)> {
+        let mut v = Soa4::new();
+        v.par_extend(par_iter);
+        v
+    }
+}
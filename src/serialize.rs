@@ -0,0 +1,222 @@
+//! `serde` support for SoA types.
+//!
+//! By default an SoA serializes the way it is laid out in memory: as a struct
+//! with one sequence per array. Deserializing checks that every sequence has
+//! the same length before any SoA is built.
+//!
+//! For formats or consumers that expect one record per row, annotate the field
+//! with `#[serde(with = "soa::serialize::rows")]` to use a sequence of tuples
+//! instead.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::cmp::min;
+
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+
+#[derive(Serialize)]
+#[serde(rename = "Soa2")]
+struct Columns2Ref<'a, A: 'a, B: 'a> {
+    d0: &'a [A],
+    d1: &'a [B],
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Soa2")]
+struct Columns2<A, B> {
+    d0: Vec<A>,
+    d1: Vec<B>,
+}
+
+#[derive(Serialize)]
+#[serde(rename = "Soa3")]
+struct Columns3Ref<'a, A: 'a, B: 'a, C: 'a> {
+    d0: &'a [A],
+    d1: &'a [B],
+    d2: &'a [C],
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Soa3")]
+struct Columns3<A, B, C> {
+    d0: Vec<A>,
+    d1: Vec<B>,
+    d2: Vec<C>,
+}
+
+#[derive(Serialize)]
+#[serde(rename = "Soa4")]
+struct Columns4Ref<'a, A: 'a, B: 'a, C: 'a, D: 'a> {
+    d0: &'a [A],
+    d1: &'a [B],
+    d2: &'a [C],
+    d3: &'a [D],
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Soa4")]
+struct Columns4<A, B, C, D> {
+    d0: Vec<A>,
+    d1: Vec<B>,
+    d2: Vec<C>,
+    d3: Vec<D>,
+}
+
+fn check_lengths<E: de::Error>(lens: &[usize]) -> Result<(), E> {
+    if lens.iter().any(|&len| len != lens[0]) {
+        return Err(E::custom(format_args!("SoA arrays have unequal lengths: {:?}", lens)));
+    }
+
+    Ok(())
+}
+
+impl<A: Serialize, B: Serialize> Serialize for Soa2<A, B> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (d0, d1) = self.as_slices();
+        Columns2Ref { d0: d0, d1: d1 }.serialize(serializer)
+    }
+}
+
+impl<'de, A: Deserialize<'de>, B: Deserialize<'de>> Deserialize<'de> for Soa2<A, B> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Soa2<A, B>, D::Error> {
+        let c: Columns2<A, B> = try!(Deserialize::deserialize(deserializer));
+        try!(check_lengths(&[c.d0.len(), c.d1.len()]));
+        Ok(Soa2::from_vecs(c.d0, c.d1))
+    }
+}
+
+impl<A: Serialize, B: Serialize, C: Serialize> Serialize for Soa3<A, B, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (d0, d1, d2) = self.as_slices();
+        Columns3Ref { d0: d0, d1: d1, d2: d2 }.serialize(serializer)
+    }
+}
+
+impl<'de, A: Deserialize<'de>, B: Deserialize<'de>, C: Deserialize<'de>> Deserialize<'de> for Soa3<A, B, C> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Soa3<A, B, C>, D::Error> {
+        let c: Columns3<A, B, C> = try!(Deserialize::deserialize(deserializer));
+        try!(check_lengths(&[c.d0.len(), c.d1.len(), c.d2.len()]));
+        Ok(Soa3::from_vecs(c.d0, c.d1, c.d2))
+    }
+}
+
+impl<A: Serialize, B: Serialize, C: Serialize, D: Serialize> Serialize for Soa4<A, B, C, D> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (d0, d1, d2, d3) = self.as_slices();
+        Columns4Ref { d0: d0, d1: d1, d2: d2, d3: d3 }.serialize(serializer)
+    }
+}
+
+impl<'de, A, B, C, D> Deserialize<'de> for Soa4<A, B, C, D>
+  where A: Deserialize<'de>, B: Deserialize<'de>, C: Deserialize<'de>, D: Deserialize<'de> {
+    fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Soa4<A, B, C, D>, De::Error> {
+        let c: Columns4<A, B, C, D> = try!(Deserialize::deserialize(deserializer));
+        try!(check_lengths(&[c.d0.len(), c.d1.len(), c.d2.len(), c.d3.len()]));
+        Ok(Soa4::from_vecs(c.d0, c.d1, c.d2, c.d3))
+    }
+}
+
+/// Row-wise encoding, for use with `#[serde(with = "soa::serialize::rows")]`.
+///
+/// The SoA is written as a sequence of tuples, one per row, and read back by
+/// pushing each tuple as it is decoded.
+pub mod rows {
+    use super::*;
+
+    /// SoA types that can be encoded one row at a time.
+    pub trait SerializeRows {
+        #[doc(hidden)]
+        fn serialize_rows<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+    }
+
+    /// SoA types that can be decoded one row at a time.
+    pub trait DeserializeRows: Sized {
+        /// The tuple type of a single row.
+        type Row;
+
+        #[doc(hidden)]
+        fn with_capacity(capacity: usize) -> Self;
+
+        #[doc(hidden)]
+        fn push_row(&mut self, row: Self::Row);
+    }
+
+    impl<A: Serialize, B: Serialize> SerializeRows for Soa2<A, B> {
+        fn serialize_rows<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.zip_iter())
+        }
+    }
+
+    impl<A, B> DeserializeRows for Soa2<A, B> {
+        type Row = (A, B);
+
+        fn with_capacity(capacity: usize) -> Soa2<A, B> { Soa2::with_capacity(capacity) }
+
+        fn push_row(&mut self, row: (A, B)) { self.push(row) }
+    }
+
+    impl<A: Serialize, B: Serialize, C: Serialize> SerializeRows for Soa3<A, B, C> {
+        fn serialize_rows<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.zip_iter())
+        }
+    }
+
+    impl<A, B, C> DeserializeRows for Soa3<A, B, C> {
+        type Row = (A, B, C);
+
+        fn with_capacity(capacity: usize) -> Soa3<A, B, C> { Soa3::with_capacity(capacity) }
+
+        fn push_row(&mut self, row: (A, B, C)) { self.push(row) }
+    }
+
+    impl<A: Serialize, B: Serialize, C: Serialize, D: Serialize> SerializeRows for Soa4<A, B, C, D> {
+        fn serialize_rows<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.zip_iter())
+        }
+    }
+
+    impl<A, B, C, D> DeserializeRows for Soa4<A, B, C, D> {
+        type Row = (A, B, C, D);
+
+        fn with_capacity(capacity: usize) -> Soa4<A, B, C, D> { Soa4::with_capacity(capacity) }
+
+        fn push_row(&mut self, row: (A, B, C, D)) { self.push(row) }
+    }
+
+    /// Serializes an SoA as a sequence of row tuples.
+    pub fn serialize<T: SerializeRows, S: Serializer>(soa: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        soa.serialize_rows(serializer)
+    }
+
+    /// Deserializes an SoA from a sequence of row tuples.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+      where T: DeserializeRows, T::Row: Deserialize<'de>, D: Deserializer<'de> {
+        deserializer.deserialize_seq(RowVisitor(PhantomData))
+    }
+
+    struct RowVisitor<T>(PhantomData<T>);
+
+    impl<'de, T> Visitor<'de> for RowVisitor<T> where T: DeserializeRows, T::Row: Deserialize<'de> {
+        type Value = T;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a sequence of SoA rows")
+        }
+
+        fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<T, S::Error> {
+            // Don't trust the hint with more than a page or so of memory.
+            let mut soa = T::with_capacity(min(seq.size_hint().unwrap_or(0), 4096));
+
+            while let Some(row) = try!(seq.next_element()) {
+                soa.push_row(row);
+            }
+
+            Ok(soa)
+        }
+    }
+}
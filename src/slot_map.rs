@@ -0,0 +1,233 @@
+use core::fmt::{Debug, Formatter, Result};
+use core::iter;
+use core::slice;
+
+use table::Table;
+
+/// A stable handle to a row of a `SoaSlotMap`.
+///
+/// Keys stay valid while other rows come and go, and are never reused: once
+/// the row a key refers to is removed, looking it up returns `None`, even if a
+/// new row ends up in the same slot. A slot whose generation has run out is
+/// retired rather than wrapped around, so it is never handed out again.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct Key {
+    index:      u32,
+    generation: u32,
+}
+
+/// A slot is occupied while its generation is odd. `next` is the dense index
+/// of its row while occupied, and the next free slot otherwise. A retired slot
+/// has generation 0, which no key carries, and is left off the free list.
+#[derive(Clone, Copy)]
+struct Slot {
+    generation: u32,
+    next:       u32,
+}
+
+impl Slot {
+    #[inline]
+    fn is_occupied(&self) -> bool {
+        self.generation % 2 == 1
+    }
+}
+
+/// A slot map whose rows are stored densely in an SoA.
+///
+/// Inserting a row hands back a `Key` which can be used to find the row again
+/// in O(1). Removal is O(1) too: the removed row is `swap_remove`d out of the
+/// table, so the rows are always packed together in the arrays returned by
+/// `as_slices`, ready for bulk processing. They are not kept in any particular
+/// order.
+pub struct SoaSlotMap<T: Table> {
+    data:      T,
+    keys:      Vec<Key>,
+    slots:     Vec<Slot>,
+    free_head: u32,
+}
+
+impl<T: Table> SoaSlotMap<T> {
+    /// Constructs a new, empty slot map.
+    pub fn new() -> SoaSlotMap<T> {
+        SoaSlotMap {
+            data:      T::new(),
+            keys:      Vec::new(),
+            slots:     Vec::new(),
+            free_head: 0,
+        }
+    }
+
+    /// Returns the number of rows in the slot map.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns `true` if the slot map contains no rows.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the position of `key`'s row in the dense arrays, or `None` if
+    /// it has been removed.
+    #[inline]
+    pub fn dense_index(&self, key: Key) -> Option<usize> {
+        match self.slots.get(key.index as usize) {
+            Some(slot) if slot.generation == key.generation => Some(slot.next as usize),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if `key`'s row is still in the slot map.
+    #[inline]
+    pub fn contains_key(&self, key: Key) -> bool {
+        self.dense_index(key).is_some()
+    }
+
+    /// Adds a row to the slot map, and returns its key.
+    ///
+    /// Panics if the slot map already holds `u32::MAX` rows.
+    pub fn insert(&mut self, row: T::Row) -> Key {
+        let dense = self.keys.len();
+        assert!(dense < u32::max_value() as usize, "SoaSlotMap is full");
+
+        let index = self.free_head;
+        let key =
+            if (index as usize) < self.slots.len() {
+                let slot = &mut self.slots[index as usize];
+                self.free_head = slot.next;
+                slot.generation += 1;
+                slot.next = dense as u32;
+                Key { index: index, generation: slot.generation }
+            } else {
+                self.slots.push(Slot { generation: 1, next: dense as u32 });
+                self.free_head = self.slots.len() as u32;
+                Key { index: index, generation: 1 }
+            };
+
+        self.data.push(row);
+        self.keys.push(key);
+        key
+    }
+
+    /// Removes `key`'s row from the slot map and returns it, or `None` if it
+    /// has already been removed.
+    ///
+    /// The last row of the dense arrays is moved into the removed row's place.
+    pub fn remove(&mut self, key: Key) -> Option<T::Row> {
+        let dense =
+            match self.dense_index(key) {
+                None        => return None,
+                Some(dense) => dense,
+            };
+
+        let row = self.data.swap_remove(dense);
+        self.keys.swap_remove(dense);
+
+        if dense < self.keys.len() {
+            let moved = self.keys[dense];
+            self.slots[moved.index as usize].next = dense as u32;
+        }
+
+        let slot = &mut self.slots[key.index as usize];
+        slot.generation = slot.generation.wrapping_add(1);
+
+        // Once the generation wraps, reusing the slot could revive old keys.
+        if slot.generation != 0 {
+            slot.next = self.free_head;
+            self.free_head = key.index;
+        }
+
+        debug_assert!(!slot.is_occupied());
+        Some(row)
+    }
+
+    /// Returns `key`'s row, or `None` if it has been removed.
+    #[inline]
+    pub fn get(&self, key: Key) -> Option<T::Ref<'_>> {
+        self.dense_index(key).and_then(move |dense| self.data.get(dense))
+    }
+
+    /// Returns `key`'s row mutably, or `None` if it has been removed.
+    #[inline]
+    pub fn get_mut(&mut self, key: Key) -> Option<T::Mut<'_>> {
+        match self.dense_index(key) {
+            None        => None,
+            Some(dense) => self.data.get_mut(dense),
+        }
+    }
+
+    /// Removes every row from the slot map. Keys handed out before the clear
+    /// stay invalid afterwards.
+    pub fn clear(&mut self) {
+        while let Some(&key) = self.keys.last() {
+            self.remove(key);
+        }
+    }
+
+    /// Returns the key of each row, in the same order as the dense arrays.
+    #[inline]
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
+    }
+
+    /// Returns the table holding the rows.
+    #[inline]
+    pub fn table(&self) -> &T {
+        &self.data
+    }
+
+    /// Returns slices over the dense arrays.
+    #[inline]
+    pub fn as_slices(&self) -> T::Slices<'_> {
+        self.data.as_slices()
+    }
+
+    /// Returns mutable slices over the dense arrays.
+    ///
+    /// The rows can be changed in place, but not added or removed.
+    #[inline]
+    pub fn as_mut_slices(&mut self) -> T::MutSlices<'_> {
+        self.data.as_mut_slices()
+    }
+
+    /// Returns an iterator over every row and its key, in dense order.
+    #[inline]
+    pub fn iter(&self) -> Iter<T> {
+        Iter { data: &self.data, keys: self.keys.iter().enumerate() }
+    }
+}
+
+impl<T: Table> Default for SoaSlotMap<T> {
+    fn default() -> SoaSlotMap<T> { SoaSlotMap::new() }
+}
+
+impl<T: Table + Debug> Debug for SoaSlotMap<T> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_struct("SoaSlotMap")
+         .field("keys", &self.keys)
+         .field("data", &self.data)
+         .finish()
+    }
+}
+
+/// An iterator over the rows of a `SoaSlotMap`, and their keys.
+pub struct Iter<'a, T: 'a + Table> {
+    data: &'a T,
+    keys: iter::Enumerate<slice::Iter<'a, Key>>,
+}
+
+impl<'a, T: Table> Iterator for Iter<'a, T> {
+    type Item = (Key, T::Ref<'a>);
+
+    #[inline]
+    fn next(&mut self) -> Option<(Key, T::Ref<'a>)> {
+        self.keys.next().map(|(dense, &key)| (key, self.data.get(dense).unwrap()))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
@@ -0,0 +1,505 @@
+use collections::vec;
+
+use core::cmp::Ordering;
+use core::default::Default;
+use core::fmt::{Debug, Formatter, Result};
+use core::hash::{Hash, Hasher};
+use core::iter::{self, repeat};
+use core::mem;
+use core::ptr;
+use core::slice;
+
+use array_soa2::ArraySoa2;
+use soa2::Soa2;
+
+enum Data<A, B, const N: usize> {
+    Inline(ArraySoa2<A, B, N>),
+    Heap(Soa2<A, B>),
+}
+
+/// A struct-of-2-arrays type which stores up to `N` rows inline, and spills to
+/// the heap beyond that.
+///
+/// This is the `smallvec` to `Soa2`'s `Vec`. While there are at most `N` rows,
+/// they live in an `ArraySoa2` inside the structure. The first push past that
+/// moves every row into a heap allocated `Soa2`, and the SoA stays there from
+/// then on.
+///
+/// Either way, the arrays are aligned to 16-bytes.
+pub struct SmallSoa2<A, B, const N: usize> {
+    data: Data<A, B, N>,
+}
+
+impl<A, B, const N: usize> SmallSoa2<A, B, N> {
+    /// Constructs a new, empty `SmallSoa2`.
+    ///
+    /// The SoA will not allocate until more than `N` elements are pushed onto
+    /// it.
+    #[inline]
+    pub fn new() -> SmallSoa2<A, B, N> {
+        SmallSoa2 { data: Data::Inline(ArraySoa2::new()) }
+    }
+
+    /// Constructs a new, empty `SmallSoa2` with room for at least `capacity`
+    /// tuples of elements without reallocating.
+    ///
+    /// The SoA will only allocate if `capacity` is greater than `N`.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> SmallSoa2<A, B, N> {
+        if capacity <= N {
+            SmallSoa2::new()
+        } else {
+            SmallSoa2 { data: Data::Heap(Soa2::with_capacity(capacity)) }
+        }
+    }
+
+    /// Constructs a `SmallSoa2` which has already spilled to the heap, from a
+    /// `Soa2`.
+    #[inline]
+    pub fn from_soa2(soa: Soa2<A, B>) -> SmallSoa2<A, B, N> {
+        SmallSoa2 { data: Data::Heap(soa) }
+    }
+
+    /// Returns `true` if the elements have been moved to the heap.
+    #[inline]
+    pub fn spilled(&self) -> bool {
+        match self.data {
+            Data::Inline(_) => false,
+            Data::Heap(_)   => true,
+        }
+    }
+
+    /// Returns the number of tuples stored in the SoA.
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self.data {
+            Data::Inline(ref v) => v.len(),
+            Data::Heap(ref v)   => v.len(),
+        }
+    }
+
+    /// Returns `true` if the SoA contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of elements the SoA can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        match self.data {
+            Data::Inline(_)   => N,
+            Data::Heap(ref v) => v.capacity(),
+        }
+    }
+
+    /// Moves the inline elements to a heap allocation with room for
+    /// `capacity` rows.
+    fn spill(&mut self, capacity: usize) {
+        let heap =
+            match self.data {
+                Data::Heap(_) => return,
+                Data::Inline(ref mut v) => unsafe {
+                    let len = v.len();
+                    let mut heap = Soa2::with_capacity(capacity);
+                    {
+                        let (s0, s1) = v.as_ptrs();
+                        let (d0, d1) = heap.as_mut_ptrs();
+                        ptr::copy_nonoverlapping(s0, d0, len);
+                        ptr::copy_nonoverlapping(s1, d1, len);
+                    }
+                    // The elements have moved, so the inline SoA must not
+                    // drop them.
+                    v.set_len(0);
+                    heap.set_len(len);
+                    heap
+                },
+            };
+
+        self.data = Data::Heap(heap);
+    }
+
+    /// Reserves capacity for at least `additional` more elements to be
+    /// inserted in the given SoA, spilling to the heap if they won't fit
+    /// inline.
+    ///
+    /// Panics if the new capacity overflows `usize`.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len().checked_add(additional).expect("capacity overflow");
+
+        match self.data {
+            Data::Inline(_) => if needed > N { self.spill(needed) },
+            Data::Heap(ref mut v) => v.reserve(additional),
+        }
+    }
+
+    /// Reserves the minimum capacity for exactly `additional` more elements to
+    /// be inserted in the given SoA, spilling to the heap if they won't fit
+    /// inline.
+    ///
+    /// Panics if the new capacity overflows `usize`.
+    pub fn reserve_exact(&mut self, additional: usize) {
+        let needed = self.len().checked_add(additional).expect("capacity overflow");
+
+        match self.data {
+            Data::Inline(_) => if needed > N { self.spill(needed) },
+            Data::Heap(ref mut v) => v.reserve_exact(additional),
+        }
+    }
+
+    /// Shrinks the capacity of the SoA as much as possible.
+    ///
+    /// A spilled SoA stays on the heap, even if its elements would now fit
+    /// inline. Inline SoAs are unaffected.
+    pub fn shrink_to_fit(&mut self) {
+        match self.data {
+            Data::Inline(_)       => {},
+            Data::Heap(ref mut v) => v.shrink_to_fit(),
+        }
+    }
+
+    /// Sets the length of a SoA.
+    ///
+    /// This will explicitly set the size of the soa, without actually modifying
+    /// its buffers, so it is up to the caller to ensure that the SoA is
+    /// actually the specified size.
+    #[inline]
+    pub unsafe fn set_len(&mut self, len: usize) {
+        match self.data {
+            Data::Inline(ref mut v) => v.set_len(len),
+            Data::Heap(ref mut v)   => v.set_len(len),
+        }
+    }
+
+    /// Shorten a SoA, dropping excess elements.
+    ///
+    /// If `len` is greater than the soa's current length, this has no effect.
+    pub fn truncate(&mut self, len: usize) {
+        match self.data {
+            Data::Inline(ref mut v) => v.truncate(len),
+            Data::Heap(ref mut v)   => v.truncate(len),
+        }
+    }
+
+    /// Returns mutable slices over the SoA's elements.
+    #[inline]
+    pub fn as_mut_slices<'a>(&'a mut self) -> (&'a mut [A], &'a mut [B]) {
+        match self.data {
+            Data::Inline(ref mut v) => v.as_mut_slices(),
+            Data::Heap(ref mut v)   => v.as_mut_slices(),
+        }
+    }
+
+    /// Returns slices over the SoA's elements.
+    #[inline]
+    pub fn as_slices<'a>(&'a self) -> (&'a [A], &'a [B]) {
+        match self.data {
+            Data::Inline(ref v) => v.as_slices(),
+            Data::Heap(ref v)   => v.as_slices(),
+        }
+    }
+
+    /// Returns iterators over the SoA's elements.
+    #[inline]
+    pub fn iters(&self) -> (slice::Iter<A>, slice::Iter<B>) {
+        let (d0, d1) = self.as_slices();
+        (d0.iter(), d1.iter())
+    }
+
+    /// Returns a single iterator over the SoA's elements, zipped up.
+    #[inline]
+    pub fn zip_iter(&self) -> iter::Zip<slice::Iter<A>, slice::Iter<B>> {
+        let (d0, d1) = self.iters();
+        d0.zip(d1)
+    }
+
+    /// Returns mutable iterators over the SoA's elements.
+    #[inline]
+    pub fn iters_mut(&mut self) -> (slice::IterMut<A>, slice::IterMut<B>) {
+        let (d0, d1) = self.as_mut_slices();
+        (d0.iter_mut(), d1.iter_mut())
+    }
+
+    /// Returns a single iterator over the SoA's elements, zipped up.
+    #[inline]
+    pub fn zip_iter_mut(&mut self) -> iter::Zip<slice::IterMut<A>, slice::IterMut<B>> {
+        let (d0, d1) = self.iters_mut();
+        d0.zip(d1)
+    }
+
+    /// Returns a pair of pointers to the start of the data in an SoA.
+    #[inline]
+    pub fn as_ptrs(&self) -> (*const A, *const B) {
+        match self.data {
+            Data::Inline(ref v) => v.as_ptrs(),
+            Data::Heap(ref v)   => v.as_ptrs(),
+        }
+    }
+
+    /// Returns a pair of pointers to the start of the mutable data in an SoA.
+    #[inline]
+    pub fn as_mut_ptrs(&mut self) -> (*mut A, *mut B) {
+        match self.data {
+            Data::Inline(ref mut v) => v.as_mut_ptrs(),
+            Data::Heap(ref mut v)   => v.as_mut_ptrs(),
+        }
+    }
+
+    /// Converts the SoA into a heap allocated `Soa2`, moving the elements out
+    /// of line if they haven't been already.
+    pub fn into_soa2(mut self) -> Soa2<A, B> {
+        let len = self.len();
+        self.spill(len);
+
+        match mem::replace(&mut self.data, Data::Inline(ArraySoa2::new())) {
+            Data::Heap(v)   => v,
+            Data::Inline(_) => unreachable!(),
+        }
+    }
+
+    /// Converts an SoA into iterators for each of its arrays.
+    #[inline]
+    pub fn into_iters(self) -> (vec::IntoIter<A>, vec::IntoIter<B>) {
+        self.into_soa2().into_iters()
+    }
+
+    /// Converts an SoA into a pair of `Vec`s, moving the elements to the heap
+    /// first if they haven't been already.
+    #[inline]
+    pub fn into_vecs(self) -> (Vec<A>, Vec<B>) {
+        self.into_soa2().into_vecs()
+    }
+
+    /// Removes an element from anywhere in the SoA and returns it, replacing it
+    /// with the last element.
+    ///
+    /// This does not preserve ordering, but is O(1).
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub fn swap_remove(&mut self, index: usize) -> (A, B) {
+        match self.data {
+            Data::Inline(ref mut v) => v.swap_remove(index),
+            Data::Heap(ref mut v)   => v.swap_remove(index),
+        }
+    }
+
+    /// Inserts an element at position `index` within the SoA, shifting all
+    /// elements after position `index` one position to the right.
+    ///
+    /// Panics if `index` is greater than the SoA's length.
+    pub fn insert(&mut self, index: usize, element: (A, B)) {
+        assert!(index <= self.len());
+        self.reserve(1);
+
+        match self.data {
+            Data::Inline(ref mut v) => v.insert(index, element),
+            Data::Heap(ref mut v) =>
+                if index == v.len() {
+                    v.push(element)
+                } else {
+                    v.insert(index, element)
+                },
+        }
+    }
+
+    /// Removes and returns the elements at position `index` within the SoA,
+    /// shifting all elements after position `index` one position to the left.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> (A, B) {
+        match self.data {
+            Data::Inline(ref mut v) => v.remove(index),
+            Data::Heap(ref mut v)   => v.remove(index),
+        }
+    }
+
+    /// Returns only the element specified by the predicate.
+    ///
+    /// In other words, remove all elements `e` such that `f(&e)` returns false.
+    /// This method operates in place and preserves the order of the retained
+    /// elements.
+    pub fn retain<F>(&mut self, f: F) where F: FnMut((&A, &B)) -> bool {
+        match self.data {
+            Data::Inline(ref mut v) => v.retain(f),
+            Data::Heap(ref mut v)   => v.retain(f),
+        }
+    }
+
+    /// Appends an element to the back of a collection, spilling to the heap if
+    /// it won't fit inline.
+    ///
+    /// Panics if the number of elements in the SoA overflows a `usize`.
+    #[inline]
+    pub fn push(&mut self, value: (A, B)) {
+        let value =
+            match self.data {
+                Data::Inline(ref mut v) =>
+                    match v.try_push(value) {
+                        Ok(()) => return,
+                        Err(e) => e.into_inner(),
+                    },
+                Data::Heap(ref mut v) => return v.push(value),
+            };
+
+        self.spill(N.checked_mul(2).expect("capacity overflow").max(4));
+        self.push(value);
+    }
+
+    /// Removes the last element from a SoA and returns it, or `None` if empty.
+    #[inline]
+    pub fn pop(&mut self) -> Option<(A, B)> {
+        match self.data {
+            Data::Inline(ref mut v) => v.pop(),
+            Data::Heap(ref mut v)   => v.pop(),
+        }
+    }
+
+    /// Moves all the elements of `other` into `self`, leaving `other` empty.
+    ///
+    /// Panics if the number of elements in the SoA overflows a `usize`.
+    pub fn append(&mut self, other: &mut Self) {
+        if let (&mut Data::Heap(ref mut v), &mut Data::Heap(ref mut o)) = (&mut self.data, &mut other.data) {
+            return v.append(o);
+        }
+
+        let len = other.len();
+        self.reserve(len);
+
+        unsafe {
+            let (s0, s1) = other.as_ptrs();
+            // The elements are moved out below, so `other` must not drop them.
+            other.set_len(0);
+
+            for i in 0..len {
+                self.push((ptr::read(s0.offset(i as isize)),
+                           ptr::read(s1.offset(i as isize))));
+            }
+        }
+    }
+
+    /// Clears the SoA, removing all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Extends the SoA with the elements yielded by arbitrary iterators.
+    ///
+    /// Panics if the iterators yield a different number of elements.
+    pub fn extend<I0, I1>(&mut self, i0: I0, i1: I1)
+        where I0: Iterator<Item=A>, I1: Iterator<Item=B> {
+        let (lower, _) = i0.size_hint();
+        self.reserve(lower);
+
+        let mut i1 = i1;
+        for x0 in i0 {
+            let x1 = i1.next().expect("`extend` called with iterators with unequal size");
+            self.push((x0, x1));
+        }
+
+        assert!(i1.next().is_none(), "`extend` called with iterators with unequal size");
+    }
+
+    /// Constructs an `SmallSoa2` with elements yielded by arbitrary iterators.
+    ///
+    /// Panics if the iterators yield a different number of elements.
+    pub fn from_iters<I0, I1>(i0: I0, i1: I1) -> SmallSoa2<A, B, N>
+        where I0: Iterator<Item=A>, I1: Iterator<Item=B> {
+        let mut v = SmallSoa2::new();
+        v.extend(i0, i1);
+        v
+    }
+}
+
+impl<A: Clone, B: Clone, const N: usize> SmallSoa2<A, B, N> {
+    /// Appends all elements in slices to the SoA.
+    ///
+    /// Panics if the slices are of different lengths.
+    pub fn push_all(&mut self, x0: &[A], x1: &[B]) {
+        assert_eq!(x0.len(), x1.len());
+        self.reserve(x0.len());
+
+        match self.data {
+            Data::Inline(ref mut v) =>
+                for (a, b) in x0.iter().zip(x1.iter()) {
+                    v.push((a.clone(), b.clone()));
+                },
+            Data::Heap(ref mut v) => v.push_all(x0, x1),
+        }
+    }
+
+    /// Resizes the SoA in-place so that `len()` is equal to `new_len`.
+    ///
+    /// Calls either `extend()` or `truncate()` depending on whether `new_len` is
+    /// larger than the current value of `len()` or not.
+    pub fn resize(&mut self, new_len: usize, value: (A, B)) {
+        let len = self.len();
+
+        if new_len > len {
+            self.extend(repeat(value.0).take(new_len - len),
+                        repeat(value.1).take(new_len - len));
+        } else {
+            self.truncate(new_len);
+        }
+    }
+}
+
+impl<A: Clone, B: Clone, const N: usize> Clone for SmallSoa2<A, B, N> {
+    fn clone(&self) -> SmallSoa2<A, B, N> {
+        let mut ret = SmallSoa2::with_capacity(self.len());
+        let (d0, d1) = self.as_slices();
+        ret.push_all(d0, d1);
+        ret
+    }
+}
+
+impl<A0, B0, A1, B1, const N: usize, const M: usize> PartialEq<SmallSoa2<A1, B1, M>> for SmallSoa2<A0, B0, N>
+  where A0: PartialEq<A1>, B0: PartialEq<B1> {
+    #[inline]
+    fn eq(&self, other: &SmallSoa2<A1, B1, M>) -> bool {
+        let (a0, b0) = self.as_slices();
+        let (a1, b1) = other.as_slices();
+
+        PartialEq::eq(a0, a1) && PartialEq::eq(b0, b1)
+    }
+}
+
+impl<A: Eq, B: Eq, const N: usize> Eq for SmallSoa2<A, B, N> {}
+
+impl<A: PartialOrd, B: PartialOrd, const N: usize> PartialOrd for SmallSoa2<A, B, N> {
+    #[inline]
+    fn partial_cmp(&self, other: &SmallSoa2<A, B, N>) -> Option<Ordering> {
+        self.zip_iter().partial_cmp(other.zip_iter())
+    }
+}
+
+impl<A: Ord, B: Ord, const N: usize> Ord for SmallSoa2<A, B, N> {
+    #[inline]
+    fn cmp(&self, other: &SmallSoa2<A, B, N>) -> Ordering {
+        self.zip_iter().cmp(other.zip_iter())
+    }
+}
+
+impl<A: Hash, B: Hash, const N: usize> Hash for SmallSoa2<A, B, N> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slices().hash(state)
+    }
+}
+
+impl<A, B, const N: usize> Default for SmallSoa2<A, B, N> {
+    fn default() -> SmallSoa2<A, B, N> { SmallSoa2::new() }
+}
+
+impl<A: Debug, B: Debug, const N: usize> Debug for SmallSoa2<A, B, N> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        Debug::fmt(&self.as_slices(), f)
+    }
+}
+
+impl<A, B, const N: usize> From<Soa2<A, B>> for SmallSoa2<A, B, N> {
+    fn from(soa: Soa2<A, B>) -> SmallSoa2<A, B, N> {
+        SmallSoa2::from_soa2(soa)
+    }
+}
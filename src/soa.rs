@@ -333,7 +333,7 @@ macro_rules! gen_soa {
                     return ;
                 }
                 unsafe {
-                    let space = unadorned::calc_reserve_space(&self.e, 1);
+                    let space = unadorned::calc_reserve_space(&self.e, other.e.len);
                     $(let $nmu = self.$nm.append(&self.e, &other.$nm, &other.e, &space));+;
                     unadorned::append_update(&[$($nmu),+], &mut self.e, &mut other.e, space);
                 }
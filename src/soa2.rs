@@ -1,6 +1,6 @@
 use collections::vec;
 
-use core::cmp::Ordering;
+use core::cmp::{max, Ordering};
 use core::default::Default;
 use core::fmt::{Debug, Formatter, Result};
 use core::hash::{Hash, Hasher};
@@ -9,6 +9,8 @@ use core::mem;
 use core::ptr;
 use core::slice;
 
+use soa3::Soa3;
+use soa4::Soa4;
 use unadorned::{self, Unadorned, Extent};
 
 /// A growable struct-of-2-arrays type, with heap allocated contents.
@@ -160,6 +162,38 @@ impl<A, B> Soa2<A, B> {
         self.e.cap
     }
 
+    /// Returns the SIMD width, in elements, that the capacity is kept a
+    /// multiple of. This is `1` unless `set_lanes` has been called.
+    #[inline]
+    pub fn lanes(&self) -> usize {
+        self.e.lanes
+    }
+
+    /// Keeps the capacity of the SoA a multiple of `lanes` elements from now
+    /// on, reallocating if it isn't one already.
+    ///
+    /// This guarantees that the rows between `len()` and `padded_len()` are
+    /// always backed by the allocation, so kernels running over
+    /// `as_padded_slices` never need a scalar loop for the tail.
+    ///
+    /// Panics if `lanes` is not a power of two.
+    pub fn set_lanes(&mut self, lanes: usize) {
+        let space = unadorned::calc_set_lanes_space(&self.e, self.is_boring(), lanes);
+
+        unsafe {
+            let d0u = self.d0.set_lanes(&self.e, &space);
+            let d1u = self.d1.set_lanes(&self.e, &space);
+
+            unadorned::set_lanes_update(&[d0u, d1u], lanes, space, &mut self.e);
+        }
+    }
+
+    /// Returns the length of the SoA, rounded up to a multiple of `lanes()`.
+    #[inline]
+    pub fn padded_len(&self) -> usize {
+        unadorned::padded_len(&self.e)
+    }
+
     /// Reserves capacity for at least `additional` more elements to be inserted
     /// in the given SoA. The collection may reserve more space to avoid frequent
     /// reallocations.
@@ -251,6 +285,30 @@ impl<A, B> Soa2<A, B> {
         }
     }
 
+    /// Returns references to the elements of the `index`th row, or `None` if
+    /// it is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(&A, &B)> {
+        if index >= self.e.len { return None }
+
+        let (d0, d1) = self.as_slices();
+        unsafe {
+            Some((d0.get_unchecked(index), d1.get_unchecked(index)))
+        }
+    }
+
+    /// Returns mutable references to the elements of the `index`th row, or
+    /// `None` if it is out of bounds.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<(&mut A, &mut B)> {
+        if index >= self.e.len { return None }
+
+        let (d0, d1) = self.as_mut_slices();
+        unsafe {
+            Some((d0.get_unchecked_mut(index), d1.get_unchecked_mut(index)))
+        }
+    }
+
     /// Returns iterators over the SoA's elements.
     #[inline]
     pub fn iters(&self) -> (slice::Iter<A>, slice::Iter<B>) {
@@ -442,7 +500,7 @@ impl<A, B> Soa2<A, B> {
         }
 
         unsafe {
-            let space = unadorned::calc_reserve_space(&self.e, 1);
+            let space = unadorned::calc_reserve_space(&self.e, other.e.len);
 
             let d0u = self.d0.append(&self.e, &other.d0, &other.e, &space);
             let d1u = self.d1.append(&self.e, &other.d1, &other.e, &space);
@@ -490,6 +548,76 @@ impl<A, B> Soa2<A, B> {
     }
 
     // TODO: dedup
+
+    /// Adds `column` to the SoA as a new last array, returning the wider SoA.
+    ///
+    /// The existing arrays are moved into the new SoA as they are, and
+    /// `column`'s elements are moved into a new 16-byte aligned allocation of
+    /// the same capacity.
+    ///
+    /// Panics if `column` isn't the same length as the SoA.
+    pub fn with_column<C>(mut self, column: Vec<C>) -> Soa3<A, B, C> {
+        assert_eq!(column.len(), self.e.len);
+
+        let len = self.e.len;
+        let lanes = self.e.lanes;
+        let cap =
+            if self.is_boring() && mem::size_of::<C>() != 0 {
+                // the existing arrays take no space, so their capacity is
+                // meaningless
+                len
+            } else {
+                self.e.cap
+            };
+
+        unsafe {
+            let d2 = unadorned::column_from_vec(column, cap);
+            let (d0, d1) = self.as_mut_ptrs();
+            mem::forget(self);
+
+            let mut ret = Soa3::from_raw_parts(d0, d1, d2, len, cap);
+            ret.set_lanes(lanes);
+            ret
+        }
+    }
+
+    /// Joins the arrays of two equal-length SoAs side by side, returning a
+    /// `Soa4`. No elements are copied.
+    ///
+    /// The arrays of an SoA all share one capacity, so if the two SoAs'
+    /// capacities differ, the smaller is grown to match first.
+    ///
+    /// Panics if the SoAs' lengths differ.
+    pub fn join<C, D>(mut self, mut other: Soa2<C, D>) -> Soa4<A, B, C, D> {
+        assert_eq!(self.e.len, other.e.len);
+
+        let len = self.e.len;
+        let lanes = max(self.e.lanes, other.e.lanes);
+        let cap =
+            if self.is_boring() {
+                other.e.cap
+            } else if other.is_boring() {
+                self.e.cap
+            } else {
+                while self.e.cap != other.e.cap {
+                    let cap = max(self.e.cap, other.e.cap);
+                    self.reserve_exact(cap - len);
+                    other.reserve_exact(cap - len);
+                }
+                self.e.cap
+            };
+
+        unsafe {
+            let (d0, d1) = self.as_mut_ptrs();
+            let (d2, d3) = other.as_mut_ptrs();
+            mem::forget(self);
+            mem::forget(other);
+
+            let mut ret = Soa4::from_raw_parts(d0, d1, d2, d3, len, cap);
+            ret.set_lanes(lanes);
+            ret
+        }
+    }
 }
 
 impl<A: Clone, B: Clone> Soa2<A, B> {
@@ -527,12 +655,93 @@ impl<A: Clone, B: Clone> Soa2<A, B> {
             unadorned::push_all_update(&[d0u, d1u], &mut self.e, x0.len(), space);
         }
     }
+
+    /// Clones the SoA's elements into a `Vec` of tuples.
+    pub fn to_aos(&self) -> Vec<(A, B)> {
+        self.zip_iter().map(|(d0, d1)| (d0.clone(), d1.clone())).collect()
+    }
+}
+
+impl<A: Copy, B: Copy> Soa2<A, B> {
+    /// Returns slices over the SoA's elements, extended out to `padded_len()`
+    /// rows by filling the tail with `pad`.
+    ///
+    /// The padding rows are not part of the SoA: they are not counted by
+    /// `len()`, and the next push will overwrite them.
+    #[inline]
+    pub fn as_padded_slices<'a>(&'a mut self, pad: (A, B)) -> (&'a [A], &'a [B]) {
+        let (d0, d1) = self.as_padded_mut_slices(pad);
+        (d0, d1)
+    }
+
+    /// Returns mutable slices over the SoA's elements, extended out to
+    /// `padded_len()` rows by filling the tail with `pad`.
+    pub fn as_padded_mut_slices<'a>(&'a mut self, pad: (A, B)) -> (&'a mut [A], &'a mut [B]) {
+        unsafe {
+            self.d0.pad(pad.0, &self.e);
+            self.d1.pad(pad.1, &self.e);
+
+            let len = self.padded_len();
+            (self.d0.as_mut_slice(len), self.d1.as_mut_slice(len))
+        }
+    }
+
+    /// Constructs an SoA by transposing a slice of tuples.
+    ///
+    /// The rows are copied a block at a time, one array at a time, which keeps
+    /// each inner loop a simple strided gather the compiler can vectorize.
+    pub fn from_aos_slice(rows: &[(A, B)]) -> Soa2<A, B> {
+        let mut ret = Soa2::with_capacity(rows.len());
+
+        unsafe {
+            let (p0, p1) = ret.as_mut_ptrs();
+
+            for (b, block) in rows.chunks(unadorned::TRANSPOSE_BLOCK).enumerate() {
+                let base = b * unadorned::TRANSPOSE_BLOCK;
+                for (i, x) in block.iter().enumerate() {
+                    ptr::write(p0.offset((base + i) as isize), x.0);
+                }
+                for (i, x) in block.iter().enumerate() {
+                    ptr::write(p1.offset((base + i) as isize), x.1);
+                }
+            }
+
+            ret.set_len(rows.len());
+        }
+
+        ret
+    }
+
+    /// Copies the SoA's elements into a slice of tuples, transposing them a
+    /// block at a time like `from_aos_slice`.
+    ///
+    /// Panics if `out` isn't the same length as the SoA.
+    pub fn copy_to_aos(&self, out: &mut [(A, B)]) {
+        assert_eq!(out.len(), self.len());
+
+        unsafe {
+            let (p0, p1) = self.as_ptrs();
+
+            for (b, block) in out.chunks_mut(unadorned::TRANSPOSE_BLOCK).enumerate() {
+                let base = b * unadorned::TRANSPOSE_BLOCK;
+                let s0 = p0.offset(base as isize);
+                let s1 = p1.offset(base as isize);
+                for (i, x) in block.iter_mut().enumerate() {
+                    x.0 = *s0.offset(i as isize);
+                }
+                for (i, x) in block.iter_mut().enumerate() {
+                    x.1 = *s1.offset(i as isize);
+                }
+            }
+        }
+    }
 }
 
 impl<A: Clone, B: Clone> Clone for Soa2<A, B> {
     #[inline]
     fn clone(&self) -> Soa2<A, B> {
         let mut ret = Soa2::new();
+        ret.set_lanes(self.lanes());
         let (d0, d1) = self.as_slices();
         ret.push_all(d0, d1);
         ret
@@ -666,6 +875,52 @@ impl<A, B> Default for Soa2<A, B> {
     fn default() -> Soa2<A, B> { Soa2::new() }
 }
 
+impl<A, B> From<Vec<(A, B)>> for Soa2<A, B> {
+    /// Transposes a `Vec` of tuples into an SoA, moving the elements without
+    /// cloning them.
+    fn from(mut v: Vec<(A, B)>) -> Soa2<A, B> {
+        let len = v.len();
+        let mut ret = Soa2::with_capacity(len);
+
+        unsafe {
+            let (p0, p1) = ret.as_mut_ptrs();
+            let src = v.as_ptr();
+            v.set_len(0);
+
+            for i in 0..len {
+                let row = ptr::read(src.offset(i as isize));
+                ptr::write(p0.offset(i as isize), row.0);
+                ptr::write(p1.offset(i as isize), row.1);
+            }
+
+            ret.set_len(len);
+        }
+
+        ret
+    }
+}
+
+impl<A, B> From<Soa2<A, B>> for Vec<(A, B)> {
+    /// Transposes an SoA into a `Vec` of tuples, moving the elements without
+    /// cloning them.
+    fn from(mut soa: Soa2<A, B>) -> Vec<(A, B)> {
+        let len = soa.len();
+        let mut ret = Vec::with_capacity(len);
+
+        unsafe {
+            let (p0, p1) = soa.as_ptrs();
+            soa.set_len(0);
+
+            for i in 0..len {
+                ret.push((ptr::read(p0.offset(i as isize)),
+                          ptr::read(p1.offset(i as isize))));
+            }
+        }
+
+        ret
+    }
+}
+
 impl<A: Debug, B: Debug> Debug for Soa2<A, B> {
     fn fmt(&self, f: &mut Formatter) -> Result {
         Debug::fmt(&self.as_slices(), f)
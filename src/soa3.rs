@@ -9,6 +9,8 @@ use core::mem;
 use core::ptr;
 use core::slice;
 
+use soa2::Soa2;
+use soa4::Soa4;
 use unadorned::{self, Unadorned, Extent};
 
 /// A growable struct-of-3-arrays type, with heap allocated contents.
@@ -171,6 +173,39 @@ impl<A, B, C> Soa3<A, B, C> {
         self.e.cap
     }
 
+    /// Returns the SIMD width, in elements, that the capacity is kept a
+    /// multiple of. This is `1` unless `set_lanes` has been called.
+    #[inline]
+    pub fn lanes(&self) -> usize {
+        self.e.lanes
+    }
+
+    /// Keeps the capacity of the SoA a multiple of `lanes` elements from now
+    /// on, reallocating if it isn't one already.
+    ///
+    /// This guarantees that the rows between `len()` and `padded_len()` are
+    /// always backed by the allocation, so kernels running over
+    /// `as_padded_slices` never need a scalar loop for the tail.
+    ///
+    /// Panics if `lanes` is not a power of two.
+    pub fn set_lanes(&mut self, lanes: usize) {
+        let space = unadorned::calc_set_lanes_space(&self.e, self.is_boring(), lanes);
+
+        unsafe {
+            let d0u = self.d0.set_lanes(&self.e, &space);
+            let d1u = self.d1.set_lanes(&self.e, &space);
+            let d2u = self.d2.set_lanes(&self.e, &space);
+
+            unadorned::set_lanes_update(&[d0u, d1u, d2u], lanes, space, &mut self.e);
+        }
+    }
+
+    /// Returns the length of the SoA, rounded up to a multiple of `lanes()`.
+    #[inline]
+    pub fn padded_len(&self) -> usize {
+        unadorned::padded_len(&self.e)
+    }
+
     /// Reserves capacity for at least `additional` more elements to be inserted
     /// in the given SoA. The collection may reserve more space to avoid frequent
     /// reallocations.
@@ -270,6 +305,34 @@ impl<A, B, C> Soa3<A, B, C> {
         }
     }
 
+    /// Returns references to the elements of the `index`th row, or `None` if
+    /// it is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(&A, &B, &C)> {
+        if index >= self.e.len { return None }
+
+        let (d0, d1, d2) = self.as_slices();
+        unsafe {
+            Some((d0.get_unchecked(index),
+                  d1.get_unchecked(index),
+                  d2.get_unchecked(index)))
+        }
+    }
+
+    /// Returns mutable references to the elements of the `index`th row, or
+    /// `None` if it is out of bounds.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<(&mut A, &mut B, &mut C)> {
+        if index >= self.e.len { return None }
+
+        let (d0, d1, d2) = self.as_mut_slices();
+        unsafe {
+            Some((d0.get_unchecked_mut(index),
+                  d1.get_unchecked_mut(index),
+                  d2.get_unchecked_mut(index)))
+        }
+    }
+
     /// Returns iterators over the SoA's elements.
     #[inline]
     pub fn iters(&self) -> (slice::Iter<A>, slice::Iter<B>, slice::Iter<C>) {
@@ -476,7 +539,7 @@ impl<A, B, C> Soa3<A, B, C> {
         }
 
         unsafe {
-            let space = unadorned::calc_reserve_space(&self.e, 1);
+            let space = unadorned::calc_reserve_space(&self.e, other.e.len);
 
             let d0u = self.d0.append(&self.e, &other.d0, &other.e, &space);
             let d1u = self.d1.append(&self.e, &other.d1, &other.e, &space);
@@ -526,6 +589,53 @@ impl<A, B, C> Soa3<A, B, C> {
     }
 
     // TODO: dedup
+
+    /// Adds `column` to the SoA as a new last array, returning the wider SoA.
+    ///
+    /// The existing arrays are moved into the new SoA as they are, and
+    /// `column`'s elements are moved into a new 16-byte aligned allocation of
+    /// the same capacity.
+    ///
+    /// Panics if `column` isn't the same length as the SoA.
+    pub fn with_column<D>(mut self, column: Vec<D>) -> Soa4<A, B, C, D> {
+        assert_eq!(column.len(), self.e.len);
+
+        let len = self.e.len;
+        let lanes = self.e.lanes;
+        let cap =
+            if self.is_boring() && mem::size_of::<D>() != 0 {
+                // the existing arrays take no space, so their capacity is
+                // meaningless
+                len
+            } else {
+                self.e.cap
+            };
+
+        unsafe {
+            let d3 = unadorned::column_from_vec(column, cap);
+            let (d0, d1, d2) = self.as_mut_ptrs();
+            mem::forget(self);
+
+            let mut ret = Soa4::from_raw_parts(d0, d1, d2, d3, len, cap);
+            ret.set_lanes(lanes);
+            ret
+        }
+    }
+
+    /// Splits off the last array as a `Vec`, returning it along with a `Soa2`
+    /// of the others. No elements are copied.
+    pub fn into_parts(mut self) -> (Soa2<A, B>, Vec<C>) {
+        let e = self.e;
+
+        unsafe {
+            let (d0, d1, d2) = self.as_mut_ptrs();
+            mem::forget(self);
+
+            let mut soa = Soa2::from_raw_parts(d0, d1, e.len, e.cap);
+            soa.set_lanes(e.lanes);
+            (soa, Vec::from_raw_parts(d2, e.len, e.cap))
+        }
+    }
 }
 
 impl<A: Clone, B: Clone, C: Clone> Soa3<A, B, C> {
@@ -568,12 +678,105 @@ impl<A: Clone, B: Clone, C: Clone> Soa3<A, B, C> {
             unadorned::push_all_update(&[d0u, d1u, d2u], &mut self.e, x0.len(), space);
         }
     }
+
+    /// Clones the SoA's elements into a `Vec` of tuples.
+    pub fn to_aos(&self) -> Vec<(A, B, C)> {
+        self.zip_iter()
+            .map(|(d0, d1, d2)| (d0.clone(), d1.clone(), d2.clone()))
+            .collect()
+    }
+}
+
+impl<A: Copy, B: Copy, C: Copy> Soa3<A, B, C> {
+    /// Returns slices over the SoA's elements, extended out to `padded_len()`
+    /// rows by filling the tail with `pad`.
+    ///
+    /// The padding rows are not part of the SoA: they are not counted by
+    /// `len()`, and the next push will overwrite them.
+    #[inline]
+    pub fn as_padded_slices<'a>(&'a mut self, pad: (A, B, C)) -> (&'a [A], &'a [B], &'a [C]) {
+        let (d0, d1, d2) = self.as_padded_mut_slices(pad);
+        (d0, d1, d2)
+    }
+
+    /// Returns mutable slices over the SoA's elements, extended out to
+    /// `padded_len()` rows by filling the tail with `pad`.
+    pub fn as_padded_mut_slices<'a>(&'a mut self, pad: (A, B, C)) -> (&'a mut [A], &'a mut [B], &'a mut [C]) {
+        unsafe {
+            self.d0.pad(pad.0, &self.e);
+            self.d1.pad(pad.1, &self.e);
+            self.d2.pad(pad.2, &self.e);
+
+            let len = self.padded_len();
+            (self.d0.as_mut_slice(len),
+             self.d1.as_mut_slice(len),
+             self.d2.as_mut_slice(len))
+        }
+    }
+
+    /// Constructs an SoA by transposing a slice of tuples.
+    ///
+    /// The rows are copied a block at a time, one array at a time, which keeps
+    /// each inner loop a simple strided gather the compiler can vectorize.
+    pub fn from_aos_slice(rows: &[(A, B, C)]) -> Soa3<A, B, C> {
+        let mut ret = Soa3::with_capacity(rows.len());
+
+        unsafe {
+            let (p0, p1, p2) = ret.as_mut_ptrs();
+
+            for (b, block) in rows.chunks(unadorned::TRANSPOSE_BLOCK).enumerate() {
+                let base = b * unadorned::TRANSPOSE_BLOCK;
+                for (i, x) in block.iter().enumerate() {
+                    ptr::write(p0.offset((base + i) as isize), x.0);
+                }
+                for (i, x) in block.iter().enumerate() {
+                    ptr::write(p1.offset((base + i) as isize), x.1);
+                }
+                for (i, x) in block.iter().enumerate() {
+                    ptr::write(p2.offset((base + i) as isize), x.2);
+                }
+            }
+
+            ret.set_len(rows.len());
+        }
+
+        ret
+    }
+
+    /// Copies the SoA's elements into a slice of tuples, transposing them a
+    /// block at a time like `from_aos_slice`.
+    ///
+    /// Panics if `out` isn't the same length as the SoA.
+    pub fn copy_to_aos(&self, out: &mut [(A, B, C)]) {
+        assert_eq!(out.len(), self.len());
+
+        unsafe {
+            let (p0, p1, p2) = self.as_ptrs();
+
+            for (b, block) in out.chunks_mut(unadorned::TRANSPOSE_BLOCK).enumerate() {
+                let base = b * unadorned::TRANSPOSE_BLOCK;
+                let s0 = p0.offset(base as isize);
+                let s1 = p1.offset(base as isize);
+                let s2 = p2.offset(base as isize);
+                for (i, x) in block.iter_mut().enumerate() {
+                    x.0 = *s0.offset(i as isize);
+                }
+                for (i, x) in block.iter_mut().enumerate() {
+                    x.1 = *s1.offset(i as isize);
+                }
+                for (i, x) in block.iter_mut().enumerate() {
+                    x.2 = *s2.offset(i as isize);
+                }
+            }
+        }
+    }
 }
 
 impl<A: Clone, B: Clone, C: Clone> Clone for Soa3<A, B, C> {
     #[inline]
     fn clone(&self) -> Soa3<A, B, C> {
         let mut ret = Soa3::new();
+        ret.set_lanes(self.lanes());
         let (d0, d1, d2) = self.as_slices();
         ret.push_all(d0, d1, d2);
         ret
@@ -712,6 +915,54 @@ impl<A, B, C> Default for Soa3<A, B, C> {
     fn default() -> Soa3<A, B, C> { Soa3::new() }
 }
 
+impl<A, B, C> From<Vec<(A, B, C)>> for Soa3<A, B, C> {
+    /// Transposes a `Vec` of tuples into an SoA, moving the elements without
+    /// cloning them.
+    fn from(mut v: Vec<(A, B, C)>) -> Soa3<A, B, C> {
+        let len = v.len();
+        let mut ret = Soa3::with_capacity(len);
+
+        unsafe {
+            let (p0, p1, p2) = ret.as_mut_ptrs();
+            let src = v.as_ptr();
+            v.set_len(0);
+
+            for i in 0..len {
+                let row = ptr::read(src.offset(i as isize));
+                ptr::write(p0.offset(i as isize), row.0);
+                ptr::write(p1.offset(i as isize), row.1);
+                ptr::write(p2.offset(i as isize), row.2);
+            }
+
+            ret.set_len(len);
+        }
+
+        ret
+    }
+}
+
+impl<A, B, C> From<Soa3<A, B, C>> for Vec<(A, B, C)> {
+    /// Transposes an SoA into a `Vec` of tuples, moving the elements without
+    /// cloning them.
+    fn from(mut soa: Soa3<A, B, C>) -> Vec<(A, B, C)> {
+        let len = soa.len();
+        let mut ret = Vec::with_capacity(len);
+
+        unsafe {
+            let (p0, p1, p2) = soa.as_ptrs();
+            soa.set_len(0);
+
+            for i in 0..len {
+                ret.push((ptr::read(p0.offset(i as isize)),
+                          ptr::read(p1.offset(i as isize)),
+                          ptr::read(p2.offset(i as isize))));
+            }
+        }
+
+        ret
+    }
+}
+
 impl<A: Debug, B: Debug, C: Debug> Debug for Soa3<A, B, C> {
     fn fmt(&self, f: &mut Formatter) -> Result {
         Debug::fmt(&self.as_slices(), f)
@@ -9,6 +9,7 @@ use core::mem;
 use core::ptr;
 use core::slice;
 
+use soa2::Soa2;
 use unadorned::{self, Unadorned, Extent};
 
 /// A growable struct-of-4-arrays type, with heap allocated contents.
@@ -184,6 +185,40 @@ impl<A, B, C, D> Soa4<A, B, C, D> {
         self.e.cap
     }
 
+    /// Returns the SIMD width, in elements, that the capacity is kept a
+    /// multiple of. This is `1` unless `set_lanes` has been called.
+    #[inline]
+    pub fn lanes(&self) -> usize {
+        self.e.lanes
+    }
+
+    /// Keeps the capacity of the SoA a multiple of `lanes` elements from now
+    /// on, reallocating if it isn't one already.
+    ///
+    /// This guarantees that the rows between `len()` and `padded_len()` are
+    /// always backed by the allocation, so kernels running over
+    /// `as_padded_slices` never need a scalar loop for the tail.
+    ///
+    /// Panics if `lanes` is not a power of two.
+    pub fn set_lanes(&mut self, lanes: usize) {
+        let space = unadorned::calc_set_lanes_space(&self.e, self.is_boring(), lanes);
+
+        unsafe {
+            let d0u = self.d0.set_lanes(&self.e, &space);
+            let d1u = self.d1.set_lanes(&self.e, &space);
+            let d2u = self.d2.set_lanes(&self.e, &space);
+            let d3u = self.d3.set_lanes(&self.e, &space);
+
+            unadorned::set_lanes_update(&[d0u, d1u, d2u, d3u], lanes, space, &mut self.e);
+        }
+    }
+
+    /// Returns the length of the SoA, rounded up to a multiple of `lanes()`.
+    #[inline]
+    pub fn padded_len(&self) -> usize {
+        unadorned::padded_len(&self.e)
+    }
+
     /// Reserves capacity for at least `additional` more elements to be inserted
     /// in the given SoA. The collection may reserve more space to avoid frequent
     /// reallocations.
@@ -289,6 +324,36 @@ impl<A, B, C, D> Soa4<A, B, C, D> {
         }
     }
 
+    /// Returns references to the elements of the `index`th row, or `None` if
+    /// it is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(&A, &B, &C, &D)> {
+        if index >= self.e.len { return None }
+
+        let (d0, d1, d2, d3) = self.as_slices();
+        unsafe {
+            Some((d0.get_unchecked(index),
+                  d1.get_unchecked(index),
+                  d2.get_unchecked(index),
+                  d3.get_unchecked(index)))
+        }
+    }
+
+    /// Returns mutable references to the elements of the `index`th row, or
+    /// `None` if it is out of bounds.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<(&mut A, &mut B, &mut C, &mut D)> {
+        if index >= self.e.len { return None }
+
+        let (d0, d1, d2, d3) = self.as_mut_slices();
+        unsafe {
+            Some((d0.get_unchecked_mut(index),
+                  d1.get_unchecked_mut(index),
+                  d2.get_unchecked_mut(index),
+                  d3.get_unchecked_mut(index)))
+        }
+    }
+
     /// Returns iterators over the SoA's elements.
     #[inline]
     pub fn iters(&self) -> (slice::Iter<A>, slice::Iter<B>, slice::Iter<C>, slice::Iter<D>) {
@@ -505,7 +570,7 @@ impl<A, B, C, D> Soa4<A, B, C, D> {
         }
 
         unsafe {
-            let space = unadorned::calc_reserve_space(&self.e, 1);
+            let space = unadorned::calc_reserve_space(&self.e, other.e.len);
 
             let d0u = self.d0.append(&self.e, &other.d0, &other.e, &space);
             let d1u = self.d1.append(&self.e, &other.d1, &other.e, &space);
@@ -557,6 +622,23 @@ impl<A, B, C, D> Soa4<A, B, C, D> {
     }
 
     // TODO: dedup
+
+    /// Splits the SoA down the middle into two `Soa2`s, undoing `Soa2::join`.
+    /// No elements are copied.
+    pub fn into_parts(mut self) -> (Soa2<A, B>, Soa2<C, D>) {
+        let e = self.e;
+
+        unsafe {
+            let (d0, d1, d2, d3) = self.as_mut_ptrs();
+            mem::forget(self);
+
+            let mut left = Soa2::from_raw_parts(d0, d1, e.len, e.cap);
+            let mut right = Soa2::from_raw_parts(d2, d3, e.len, e.cap);
+            left.set_lanes(e.lanes);
+            right.set_lanes(e.lanes);
+            (left, right)
+        }
+    }
 }
 
 impl<A: Clone, B: Clone, C: Clone, D: Clone> Soa4<A, B, C, D> {
@@ -602,12 +684,114 @@ impl<A: Clone, B: Clone, C: Clone, D: Clone> Soa4<A, B, C, D> {
             unadorned::push_all_update(&[d0u, d1u, d2u, d3u], &mut self.e, x0.len(), space);
         }
     }
+
+    /// Clones the SoA's elements into a `Vec` of tuples.
+    pub fn to_aos(&self) -> Vec<(A, B, C, D)> {
+        self.zip_iter()
+            .map(|(d0, d1, d2, d3)| (d0.clone(), d1.clone(), d2.clone(), d3.clone()))
+            .collect()
+    }
+}
+
+impl<A: Copy, B: Copy, C: Copy, D: Copy> Soa4<A, B, C, D> {
+    /// Returns slices over the SoA's elements, extended out to `padded_len()`
+    /// rows by filling the tail with `pad`.
+    ///
+    /// The padding rows are not part of the SoA: they are not counted by
+    /// `len()`, and the next push will overwrite them.
+    #[inline]
+    pub fn as_padded_slices<'a>(&'a mut self, pad: (A, B, C, D)) -> (&'a [A], &'a [B], &'a [C], &'a [D]) {
+        let (d0, d1, d2, d3) = self.as_padded_mut_slices(pad);
+        (d0, d1, d2, d3)
+    }
+
+    /// Returns mutable slices over the SoA's elements, extended out to
+    /// `padded_len()` rows by filling the tail with `pad`.
+    pub fn as_padded_mut_slices<'a>(&'a mut self, pad: (A, B, C, D)) -> (&'a mut [A], &'a mut [B], &'a mut [C], &'a mut [D]) {
+        unsafe {
+            self.d0.pad(pad.0, &self.e);
+            self.d1.pad(pad.1, &self.e);
+            self.d2.pad(pad.2, &self.e);
+            self.d3.pad(pad.3, &self.e);
+
+            let len = self.padded_len();
+            (self.d0.as_mut_slice(len),
+             self.d1.as_mut_slice(len),
+             self.d2.as_mut_slice(len),
+             self.d3.as_mut_slice(len))
+        }
+    }
+
+    /// Constructs an SoA by transposing a slice of tuples.
+    ///
+    /// The rows are copied a block at a time, one array at a time, which keeps
+    /// each inner loop a simple strided gather the compiler can vectorize.
+    pub fn from_aos_slice(rows: &[(A, B, C, D)]) -> Soa4<A, B, C, D> {
+        let mut ret = Soa4::with_capacity(rows.len());
+
+        unsafe {
+            let (p0, p1, p2, p3) = ret.as_mut_ptrs();
+
+            for (b, block) in rows.chunks(unadorned::TRANSPOSE_BLOCK).enumerate() {
+                let base = b * unadorned::TRANSPOSE_BLOCK;
+                for (i, x) in block.iter().enumerate() {
+                    ptr::write(p0.offset((base + i) as isize), x.0);
+                }
+                for (i, x) in block.iter().enumerate() {
+                    ptr::write(p1.offset((base + i) as isize), x.1);
+                }
+                for (i, x) in block.iter().enumerate() {
+                    ptr::write(p2.offset((base + i) as isize), x.2);
+                }
+                for (i, x) in block.iter().enumerate() {
+                    ptr::write(p3.offset((base + i) as isize), x.3);
+                }
+            }
+
+            ret.set_len(rows.len());
+        }
+
+        ret
+    }
+
+    /// Copies the SoA's elements into a slice of tuples, transposing them a
+    /// block at a time like `from_aos_slice`.
+    ///
+    /// Panics if `out` isn't the same length as the SoA.
+    pub fn copy_to_aos(&self, out: &mut [(A, B, C, D)]) {
+        assert_eq!(out.len(), self.len());
+
+        unsafe {
+            let (p0, p1, p2, p3) = self.as_ptrs();
+
+            for (b, block) in out.chunks_mut(unadorned::TRANSPOSE_BLOCK).enumerate() {
+                let base = b * unadorned::TRANSPOSE_BLOCK;
+                let s0 = p0.offset(base as isize);
+                let s1 = p1.offset(base as isize);
+                let s2 = p2.offset(base as isize);
+                let s3 = p3.offset(base as isize);
+                for (i, x) in block.iter_mut().enumerate() {
+                    x.0 = *s0.offset(i as isize);
+                }
+                for (i, x) in block.iter_mut().enumerate() {
+                    x.1 = *s1.offset(i as isize);
+                }
+                for (i, x) in block.iter_mut().enumerate() {
+                    x.2 = *s2.offset(i as isize);
+                }
+                for (i, x) in block.iter_mut().enumerate() {
+                    x.3 = *s3.offset(i as isize);
+                }
+            }
+        }
+    }
 }
 
 impl<A: Clone, B: Clone, C: Clone, D: Clone> Clone for Soa4<A, B, C, D> {
     #[inline]
     fn clone(&self) -> Soa4<A, B, C, D> {
         let mut ret = Soa4::new();
+        ret.set_lanes(self.lanes());
         let (d0, d1, d2, d3) = self.as_slices();
         ret.push_all(d0, d1, d2, d3);
         ret
@@ -751,6 +935,56 @@ impl<A, B, C, D> Default for Soa4<A, B, C, D> {
     fn default() -> Soa4<A, B, C, D> { Soa4::new() }
 }
 
+impl<A, B, C, D> From<Vec<(A, B, C, D)>> for Soa4<A, B, C, D> {
+    /// Transposes a `Vec` of tuples into an SoA, moving the elements without
+    /// cloning them.
+    fn from(mut v: Vec<(A, B, C, D)>) -> Soa4<A, B, C, D> {
+        let len = v.len();
+        let mut ret = Soa4::with_capacity(len);
+
+        unsafe {
+            let (p0, p1, p2, p3) = ret.as_mut_ptrs();
+            let src = v.as_ptr();
+            v.set_len(0);
+
+            for i in 0..len {
+                let row = ptr::read(src.offset(i as isize));
+                ptr::write(p0.offset(i as isize), row.0);
+                ptr::write(p1.offset(i as isize), row.1);
+                ptr::write(p2.offset(i as isize), row.2);
+                ptr::write(p3.offset(i as isize), row.3);
+            }
+
+            ret.set_len(len);
+        }
+
+        ret
+    }
+}
+
+impl<A, B, C, D> From<Soa4<A, B, C, D>> for Vec<(A, B, C, D)> {
+    /// Transposes an SoA into a `Vec` of tuples, moving the elements without
+    /// cloning them.
+    fn from(mut soa: Soa4<A, B, C, D>) -> Vec<(A, B, C, D)> {
+        let len = soa.len();
+        let mut ret = Vec::with_capacity(len);
+
+        unsafe {
+            let (p0, p1, p2, p3) = soa.as_ptrs();
+            soa.set_len(0);
+
+            for i in 0..len {
+                ret.push((ptr::read(p0.offset(i as isize)),
+                          ptr::read(p1.offset(i as isize)),
+                          ptr::read(p2.offset(i as isize)),
+                          ptr::read(p3.offset(i as isize))));
+            }
+        }
+
+        ret
+    }
+}
+
 impl<A: Debug, B: Debug, C: Debug, D: Debug> Debug for Soa4<A, B, C, D> {
     fn fmt(&self, f: &mut Formatter) -> Result {
         Debug::fmt(&self.as_slices(), f)
@@ -0,0 +1,415 @@
+use core::default::Default;
+use core::fmt::{Debug, Formatter, Result};
+use core::iter;
+use core::mem;
+use core::ptr;
+use core::slice;
+
+use soa2::Soa2;
+use unadorned::{self, Unadorned, Extent};
+
+/// A double-ended queue of pairs, stored as a struct-of-2-arrays.
+///
+/// This is the `VecDeque` to `Soa2`'s `Vec`. Each array is a ring buffer with
+/// the same capacity, and the same head and length, so rows can be pushed and
+/// popped at either end in O(1) without moving any other rows.
+///
+/// Since the rows may wrap around the end of the buffers, `as_slices` returns
+/// two slices per array. `make_contiguous` rearranges the rows so that they
+/// don't, after which the first slice of each array holds every row.
+///
+/// All data is aligned to 16-bytes.
+#[unsafe_no_drop_flag]
+pub struct SoaDeque2<A, B> {
+    d0:   Unadorned<A>,
+    d1:   Unadorned<B>,
+    head: usize,
+    e:    Extent,
+}
+
+/// Copies `n` rows of one array from `src` to `dst`, which may overlap.
+#[inline]
+unsafe fn shift<T>(p: *mut T, src: usize, dst: usize, n: usize) {
+    ptr::copy(p.offset(src as isize), p.offset(dst as isize), n);
+}
+
+impl<A, B> SoaDeque2<A, B> {
+    /// Constructs a new, empty `SoaDeque2`.
+    ///
+    /// The deque will not allocate until elements are pushed onto it.
+    pub fn new() -> SoaDeque2<A, B> {
+        unsafe {
+            let (d0, d0u) = Unadorned::new();
+            let (d1, d1u) = Unadorned::new();
+
+            let e = unadorned::new_update(&[d0u, d1u]);
+
+            SoaDeque2 { d0: d0, d1: d1, head: 0, e: e }
+        }
+    }
+
+    /// Constructs a new, empty `SoaDeque2` with room for `capacity` tuples of
+    /// elements without reallocating.
+    pub fn with_capacity(capacity: usize) -> SoaDeque2<A, B> {
+        unsafe {
+            let (d0, d0u) = Unadorned::with_capacity(capacity);
+            let (d1, d1u) = Unadorned::with_capacity(capacity);
+
+            let is_boring =
+                mem::size_of::<A>() == 0
+             && mem::size_of::<B>() == 0;
+
+            let e = unadorned::with_capacity_update(&[d0u, d1u], is_boring, capacity);
+
+            SoaDeque2 { d0: d0, d1: d1, head: 0, e: e }
+        }
+    }
+
+    /// Returns the number of tuples stored in the deque.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.e.len
+    }
+
+    /// Returns `true` if the deque contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.e.len == 0
+    }
+
+    /// Returns the number of elements the deque can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.e.cap
+    }
+
+    /// Returns `true` if the rows don't wrap around the end of the buffers.
+    #[inline]
+    fn is_contiguous(&self) -> bool {
+        self.head <= self.e.cap.saturating_sub(self.e.len)
+    }
+
+    /// Returns the buffer index of the `i`th row.
+    #[inline]
+    fn wrap_add(&self, i: usize) -> usize {
+        let cap = self.e.cap;
+        if self.head >= cap - i { self.head - (cap - i) } else { self.head + i }
+    }
+
+    /// Reserves capacity for at least `additional` more elements to be
+    /// inserted in the deque.
+    ///
+    /// Panics if the new capacity overflows `usize`.
+    pub fn reserve(&mut self, additional: usize) {
+        let space =
+            match unadorned::calc_reserve_space(&self.e, additional) {
+                None        => return,
+                Some(space) => space,
+            };
+
+        let old_cap = self.e.cap;
+        let was_contiguous = self.is_contiguous();
+
+        unsafe {
+            let d0u = self.d0.reserve(&self.e, &space);
+            let d1u = self.d1.reserve(&self.e, &space);
+
+            unadorned::reserve_update(&[d0u, d1u], space, &mut self.e);
+
+            if was_contiguous { return }
+
+            // The rows wrapped around the end of the old buffers. Move the
+            // wrapped rows up past the old end if they're the shorter part and
+            // fit there, and otherwise move the front rows up to the new end.
+            let head_len = old_cap - self.head;
+            let tail_len = self.e.len - head_len;
+
+            if tail_len < head_len && tail_len <= self.e.cap - old_cap {
+                shift(self.d0.as_mut_ptr(), 0, old_cap, tail_len);
+                shift(self.d1.as_mut_ptr(), 0, old_cap, tail_len);
+            } else {
+                let new_head = self.e.cap - head_len;
+                shift(self.d0.as_mut_ptr(), self.head, new_head, head_len);
+                shift(self.d1.as_mut_ptr(), self.head, new_head, head_len);
+                self.head = new_head;
+            }
+        }
+    }
+
+    /// Returns the rows of the deque, as a pair of slices for each array. The
+    /// first slice of each array holds the front of the deque, and the second
+    /// holds whatever wrapped around to the start of the buffer.
+    #[inline]
+    pub fn as_slices<'a>(&'a self) -> ((&'a [A], &'a [A]), (&'a [B], &'a [B])) {
+        unsafe {
+            let (p0, p1) = (self.d0.as_ptr(), self.d1.as_ptr());
+            let (head_len, tail_len) = self.segment_lens();
+            let head = self.head as isize;
+
+            ((slice::from_raw_parts(p0.offset(head), head_len), slice::from_raw_parts(p0, tail_len)),
+             (slice::from_raw_parts(p1.offset(head), head_len), slice::from_raw_parts(p1, tail_len)))
+        }
+    }
+
+    /// Returns the rows of the deque as a pair of mutable slices for each
+    /// array, split the same way as `as_slices`.
+    #[inline]
+    pub fn as_mut_slices<'a>(&'a mut self) -> ((&'a mut [A], &'a mut [A]), (&'a mut [B], &'a mut [B])) {
+        unsafe {
+            let (p0, p1) = (self.d0.as_mut_ptr(), self.d1.as_mut_ptr());
+            let (head_len, tail_len) = self.segment_lens();
+            let head = self.head as isize;
+
+            ((slice::from_raw_parts_mut(p0.offset(head), head_len), slice::from_raw_parts_mut(p0, tail_len)),
+             (slice::from_raw_parts_mut(p1.offset(head), head_len), slice::from_raw_parts_mut(p1, tail_len)))
+        }
+    }
+
+    /// Returns the number of rows before and after the wrap around point.
+    #[inline]
+    fn segment_lens(&self) -> (usize, usize) {
+        if self.is_contiguous() {
+            (self.e.len, 0)
+        } else {
+            let head_len = self.e.cap - self.head;
+            (head_len, self.e.len - head_len)
+        }
+    }
+
+    /// Rearranges the deque so that its rows don't wrap around the end of the
+    /// buffers, and returns them as a single slice per array.
+    pub fn make_contiguous<'a>(&'a mut self) -> (&'a mut [A], &'a mut [B]) {
+        if !self.is_contiguous() {
+            unsafe { self.unwrap_rows() }
+        }
+
+        let ((d0, _), (d1, _)) = self.as_mut_slices();
+        (d0, d1)
+    }
+
+    unsafe fn unwrap_rows(&mut self) {
+        let cap = self.e.cap;
+        let (head_len, tail_len) = self.segment_lens();
+        let free = cap - self.e.len;
+        let (p0, p1) = (self.d0.as_mut_ptr(), self.d1.as_mut_ptr());
+
+        if free >= head_len {
+            // Slide the wrapped rows up, then move the front rows in below them.
+            shift(p0, 0, head_len, tail_len);
+            shift(p1, 0, head_len, tail_len);
+            shift(p0, self.head, 0, head_len);
+            shift(p1, self.head, 0, head_len);
+            self.head = 0;
+        } else if free >= tail_len {
+            // Slide the front rows down, then move the wrapped rows in above
+            // them.
+            shift(p0, self.head, tail_len, head_len);
+            shift(p1, self.head, tail_len, head_len);
+            shift(p0, 0, tail_len + head_len, tail_len);
+            shift(p1, 0, tail_len + head_len, tail_len);
+            self.head = tail_len;
+        } else {
+            // Not enough room to do it in place. Copy everything into fresh
+            // buffers, in order.
+            let (mut n0, _) = Unadorned::with_capacity(cap);
+            let (mut n1, _) = Unadorned::with_capacity(cap);
+
+            let (q0, q1) = (n0.as_mut_ptr(), n1.as_mut_ptr());
+            ptr::copy_nonoverlapping(p0.offset(self.head as isize), q0, head_len);
+            ptr::copy_nonoverlapping(p1.offset(self.head as isize), q1, head_len);
+            ptr::copy_nonoverlapping(p0, q0.offset(head_len as isize), tail_len);
+            ptr::copy_nonoverlapping(p1, q1.offset(head_len as isize), tail_len);
+
+            // Free the old buffers without dropping the rows we just moved.
+            let moved = Extent { len: 0, cap: cap, lanes: 1 };
+            mem::replace(&mut self.d0, n0).drop(&moved);
+            mem::replace(&mut self.d1, n1).drop(&moved);
+            self.head = 0;
+        }
+    }
+
+    /// Returns a reference to the `index`th row, counting from the front, or
+    /// `None` if it is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(&A, &B)> {
+        if index >= self.e.len { return None }
+
+        unsafe {
+            let i = self.wrap_add(index) as isize;
+            Some((&*self.d0.as_ptr().offset(i), &*self.d1.as_ptr().offset(i)))
+        }
+    }
+
+    /// Returns a mutable reference to the `index`th row, counting from the
+    /// front, or `None` if it is out of bounds.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<(&mut A, &mut B)> {
+        if index >= self.e.len { return None }
+
+        unsafe {
+            let i = self.wrap_add(index) as isize;
+            Some((&mut *self.d0.as_mut_ptr().offset(i), &mut *self.d1.as_mut_ptr().offset(i)))
+        }
+    }
+
+    /// Returns a reference to the front row, or `None` if the deque is empty.
+    #[inline]
+    pub fn front(&self) -> Option<(&A, &B)> {
+        self.get(0)
+    }
+
+    /// Returns a reference to the back row, or `None` if the deque is empty.
+    #[inline]
+    pub fn back(&self) -> Option<(&A, &B)> {
+        if self.e.len == 0 { None } else { self.get(self.e.len - 1) }
+    }
+
+    /// Returns an iterator over the rows, front to back.
+    #[inline]
+    pub fn zip_iter(&self) -> iter::Chain<iter::Zip<slice::Iter<A>, slice::Iter<B>>, iter::Zip<slice::Iter<A>, slice::Iter<B>>> {
+        let ((h0, t0), (h1, t1)) = self.as_slices();
+        h0.iter().zip(h1.iter()).chain(t0.iter().zip(t1.iter()))
+    }
+
+    /// Appends a row to the back of the deque.
+    ///
+    /// Panics if the number of elements in the deque overflows a `usize`.
+    pub fn push_back(&mut self, value: (A, B)) {
+        if self.e.len == self.e.cap { self.reserve(1) }
+
+        unsafe {
+            let i = self.wrap_add(self.e.len) as isize;
+            ptr::write(self.d0.as_mut_ptr().offset(i), value.0);
+            ptr::write(self.d1.as_mut_ptr().offset(i), value.1);
+        }
+
+        self.e.len += 1;
+    }
+
+    /// Prepends a row to the front of the deque.
+    ///
+    /// Panics if the number of elements in the deque overflows a `usize`.
+    pub fn push_front(&mut self, value: (A, B)) {
+        if self.e.len == self.e.cap { self.reserve(1) }
+
+        self.head = if self.head == 0 { self.e.cap - 1 } else { self.head - 1 };
+
+        unsafe {
+            let i = self.head as isize;
+            ptr::write(self.d0.as_mut_ptr().offset(i), value.0);
+            ptr::write(self.d1.as_mut_ptr().offset(i), value.1);
+        }
+
+        self.e.len += 1;
+    }
+
+    /// Removes the back row and returns it, or `None` if the deque is empty.
+    pub fn pop_back(&mut self) -> Option<(A, B)> {
+        if self.e.len == 0 { return None }
+
+        self.e.len -= 1;
+
+        unsafe {
+            let i = self.wrap_add(self.e.len) as isize;
+            Some((ptr::read(self.d0.as_ptr().offset(i)), ptr::read(self.d1.as_ptr().offset(i))))
+        }
+    }
+
+    /// Removes the front row and returns it, or `None` if the deque is empty.
+    pub fn pop_front(&mut self) -> Option<(A, B)> {
+        if self.e.len == 0 { return None }
+
+        let i = self.head as isize;
+        self.head = self.wrap_add(1);
+        self.e.len -= 1;
+
+        unsafe {
+            Some((ptr::read(self.d0.as_ptr().offset(i)), ptr::read(self.d1.as_ptr().offset(i))))
+        }
+    }
+
+    /// Shortens the deque, dropping rows from the back.
+    ///
+    /// If `len` is greater than the deque's current length, this has no
+    /// effect.
+    pub fn truncate(&mut self, len: usize) {
+        while self.e.len > len {
+            self.pop_back();
+        }
+    }
+
+    /// Clears the deque, removing all values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.truncate(0);
+        self.head = 0;
+    }
+}
+
+impl<A, B> Default for SoaDeque2<A, B> {
+    fn default() -> SoaDeque2<A, B> { SoaDeque2::new() }
+}
+
+impl<A: Debug, B: Debug> Debug for SoaDeque2<A, B> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_list().entries(self.zip_iter()).finish()
+    }
+}
+
+impl<A0, B0, A1, B1> PartialEq<SoaDeque2<A1, B1>> for SoaDeque2<A0, B0>
+  where A0: PartialEq<A1>, B0: PartialEq<B1> {
+    fn eq(&self, other: &SoaDeque2<A1, B1>) -> bool {
+        self.len() == other.len()
+        && self.zip_iter().zip(other.zip_iter()).all(
+            |((a0, b0), (a1, b1))| a0 == a1 && b0 == b1)
+    }
+}
+
+impl<A, B> From<Soa2<A, B>> for SoaDeque2<A, B> {
+    /// Turns a `Soa2` into a deque without reallocating.
+    fn from(soa: Soa2<A, B>) -> SoaDeque2<A, B> {
+        let len = soa.len();
+        let cap = soa.capacity();
+        let (v0, v1) = soa.into_vecs();
+
+        // A boring `Soa2` that was pushed onto without reserving reports a
+        // capacity smaller than its length. Every row of a boring deque is
+        // free, so give it the same capacity `with_capacity` would.
+        let is_boring =
+            mem::size_of::<A>() == 0
+         && mem::size_of::<B>() == 0;
+        let cap = if is_boring { usize::MAX } else { cap };
+
+        unsafe {
+            let (d0, d0u) = Unadorned::from_raw_parts(v0.as_ptr() as *mut A);
+            let (d1, d1u) = Unadorned::from_raw_parts(v1.as_ptr() as *mut B);
+            mem::forget(v0);
+            mem::forget(v1);
+
+            let e = unadorned::from_raw_parts_update(&[d0u, d1u], len, cap);
+
+            SoaDeque2 { d0: d0, d1: d1, head: 0, e: e }
+        }
+    }
+}
+
+impl<A, B> Drop for SoaDeque2<A, B> {
+    fn drop(&mut self) {
+        if self.e.cap != 0 && self.e.cap != mem::POST_DROP_USIZE {
+            unsafe {
+                {
+                    let ((h0, t0), (h1, t1)) = self.as_mut_slices();
+                    ptr::drop_in_place(h0);
+                    ptr::drop_in_place(t0);
+                    ptr::drop_in_place(h1);
+                    ptr::drop_in_place(t1);
+                }
+
+                let e = Extent { len: 0, cap: self.e.cap, lanes: 1 };
+                self.d0.drop(&e);
+                self.d1.drop(&e);
+            }
+            self.e.cap = 0;
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! Borrowed two-array views of an SoA, which can be picked out of a wider one.
+//!
+//! A kernel written against `Soa2Slice<A, C>` can be handed `soa.project::<0,
+//! 2>()` of a `Soa4<A, B, C, D>` without copying either array.
+
+use core::fmt::{Debug, Formatter, Result};
+use core::iter;
+use core::slice;
+
+use column::{Column, ColumnMut};
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+
+/// A borrowed view of two equal-length arrays.
+#[derive(Clone, Copy)]
+pub struct Soa2Slice<'a, A: 'a, B: 'a> {
+    d0: &'a [A],
+    d1: &'a [B],
+}
+
+impl<'a, A, B> Soa2Slice<'a, A, B> {
+    /// Constructs a view of two slices.
+    ///
+    /// Panics if the slices are of different lengths.
+    #[inline]
+    pub fn new(d0: &'a [A], d1: &'a [B]) -> Soa2Slice<'a, A, B> {
+        assert_eq!(d0.len(), d1.len());
+        Soa2Slice { d0: d0, d1: d1 }
+    }
+
+    /// Returns the number of tuples in the view.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.d0.len()
+    }
+
+    /// Returns `true` if the view contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.d0.is_empty()
+    }
+
+    /// Returns the view's arrays.
+    #[inline]
+    pub fn as_slices(&self) -> (&'a [A], &'a [B]) {
+        (self.d0, self.d1)
+    }
+
+    /// Returns the `index`th row, or `None` if it is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<(&'a A, &'a B)> {
+        if index >= self.len() { return None }
+        unsafe { Some((self.d0.get_unchecked(index), self.d1.get_unchecked(index))) }
+    }
+
+    /// Returns an iterator over the view's elements, as tuples of references.
+    #[inline]
+    pub fn zip_iter(&self) -> iter::Zip<slice::Iter<'a, A>, slice::Iter<'a, B>> {
+        self.d0.iter().zip(self.d1.iter())
+    }
+}
+
+impl<'a, A: Clone, B: Clone> Soa2Slice<'a, A, B> {
+    /// Copies the view's elements into a new `Soa2`.
+    pub fn to_soa(&self) -> Soa2<A, B> {
+        let mut ret = Soa2::with_capacity(self.len());
+        ret.push_all(self.d0, self.d1);
+        ret
+    }
+}
+
+impl<'a, A: Debug, B: Debug> Debug for Soa2Slice<'a, A, B> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        Debug::fmt(&self.as_slices(), f)
+    }
+}
+
+/// A mutable borrowed view of two equal-length arrays.
+pub struct Soa2SliceMut<'a, A: 'a, B: 'a> {
+    d0: &'a mut [A],
+    d1: &'a mut [B],
+}
+
+impl<'a, A, B> Soa2SliceMut<'a, A, B> {
+    /// Constructs a mutable view of two slices.
+    ///
+    /// Panics if the slices are of different lengths.
+    #[inline]
+    pub fn new(d0: &'a mut [A], d1: &'a mut [B]) -> Soa2SliceMut<'a, A, B> {
+        assert_eq!(d0.len(), d1.len());
+        Soa2SliceMut { d0: d0, d1: d1 }
+    }
+
+    /// Returns the number of tuples in the view.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.d0.len()
+    }
+
+    /// Returns `true` if the view contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.d0.is_empty()
+    }
+
+    /// Returns the view's arrays.
+    #[inline]
+    pub fn as_slices(&self) -> (&[A], &[B]) {
+        (self.d0, self.d1)
+    }
+
+    /// Returns the view's arrays mutably.
+    #[inline]
+    pub fn as_mut_slices(&mut self) -> (&mut [A], &mut [B]) {
+        (self.d0, self.d1)
+    }
+
+    /// Consumes the view, returning its arrays with the full borrow's lifetime.
+    #[inline]
+    pub fn into_mut_slices(self) -> (&'a mut [A], &'a mut [B]) {
+        (self.d0, self.d1)
+    }
+
+    /// Reborrows the view immutably.
+    #[inline]
+    pub fn as_soa_slice(&self) -> Soa2Slice<A, B> {
+        Soa2Slice { d0: self.d0, d1: self.d1 }
+    }
+
+    /// Returns the `index`th row mutably, or `None` if it is out of bounds.
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<(&mut A, &mut B)> {
+        if index >= self.len() { return None }
+        unsafe { Some((self.d0.get_unchecked_mut(index), self.d1.get_unchecked_mut(index))) }
+    }
+
+    /// Returns an iterator over the view's elements, as tuples of mutable
+    /// references.
+    #[inline]
+    pub fn zip_iter_mut(&mut self) -> iter::Zip<slice::IterMut<A>, slice::IterMut<B>> {
+        self.d0.iter_mut().zip(self.d1.iter_mut())
+    }
+}
+
+impl<'a, A: Debug, B: Debug> Debug for Soa2SliceMut<'a, A, B> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        Debug::fmt(&self.as_slices(), f)
+    }
+}
+
+macro_rules! project {
+    ($soa:ident<$($ty:ident),+>) => {
+        impl<$($ty),+> $soa<$($ty),+> {
+            /// Returns a view of the `I`th and `J`th arrays of the SoA.
+            #[inline]
+            pub fn project<const I: usize, const J: usize>(&self)
+                -> Soa2Slice<<Self as Column<I>>::Elem, <Self as Column<J>>::Elem>
+              where Self: Column<I> + Column<J> {
+                Soa2Slice {
+                    d0: Column::<I>::column(self),
+                    d1: Column::<J>::column(self),
+                }
+            }
+
+            /// Returns a mutable view of the `I`th and `J`th arrays of the SoA.
+            ///
+            /// Panics if `I == J`.
+            #[inline]
+            pub fn project_mut<const I: usize, const J: usize>(&mut self)
+                -> Soa2SliceMut<<Self as Column<I>>::Elem, <Self as Column<J>>::Elem>
+              where Self: ColumnMut<I> + ColumnMut<J> {
+                assert!(I != J, "projected arrays must be distinct");
+
+                unsafe {
+                    // Each array is its own allocation, so these don't alias.
+                    let d0: *mut [<Self as Column<I>>::Elem] = ColumnMut::<I>::column_mut(self);
+                    let d1: *mut [<Self as Column<J>>::Elem] = ColumnMut::<J>::column_mut(self);
+                    Soa2SliceMut { d0: &mut *d0, d1: &mut *d1 }
+                }
+            }
+
+            /// Returns two mutable views of the SoA at once: one of the `I`th
+            /// and `J`th arrays, and one of the `K`th and `L`th.
+            ///
+            /// Panics unless all four arrays are distinct.
+            #[inline]
+            pub fn project_disjoint_mut<const I: usize, const J: usize,
+                                        const K: usize, const L: usize>(&mut self)
+                -> (Soa2SliceMut<<Self as Column<I>>::Elem, <Self as Column<J>>::Elem>,
+                    Soa2SliceMut<<Self as Column<K>>::Elem, <Self as Column<L>>::Elem>)
+              where Self: ColumnMut<I> + ColumnMut<J> + ColumnMut<K> + ColumnMut<L> {
+                assert!(I != J && I != K && I != L && J != K && J != L && K != L,
+                        "projected arrays must be distinct");
+
+                unsafe {
+                    let d0: *mut [<Self as Column<I>>::Elem] = ColumnMut::<I>::column_mut(self);
+                    let d1: *mut [<Self as Column<J>>::Elem] = ColumnMut::<J>::column_mut(self);
+                    let d2: *mut [<Self as Column<K>>::Elem] = ColumnMut::<K>::column_mut(self);
+                    let d3: *mut [<Self as Column<L>>::Elem] = ColumnMut::<L>::column_mut(self);
+                    (Soa2SliceMut { d0: &mut *d0, d1: &mut *d1 },
+                     Soa2SliceMut { d0: &mut *d2, d1: &mut *d3 })
+                }
+            }
+        }
+    }
+}
+
+project!(Soa2<A, B>);
+project!(Soa3<A, B, C>);
+project!(Soa4<A, B, C, D>);
@@ -0,0 +1,235 @@
+use core::fmt::{Debug, Formatter, Result};
+use core::slice;
+
+use table::Table;
+
+/// Marks an entity with no row in the sparse array.
+const EMPTY: u32 = !0;
+
+/// A sparse set mapping `u32` entity ids to rows of an SoA.
+///
+/// The rows are kept densely packed in a `Table`, so systems that touch every
+/// component can run straight over the columns from `as_slices`. A sparse array
+/// indexed by entity id maps each entity to its row, giving O(1) `insert`,
+/// `remove`, and `get`. Removal `swap_remove`s the row, so rows are not kept
+/// in any particular order.
+///
+/// The sparse array grows to the largest entity id ever inserted, so ids
+/// should be allocated densely.
+pub struct SparseSet<T: Table> {
+    data:     T,
+    entities: Vec<u32>,
+    sparse:   Vec<u32>,
+}
+
+impl<T: Table> SparseSet<T> {
+    /// Constructs a new, empty sparse set.
+    pub fn new() -> SparseSet<T> {
+        SparseSet {
+            data:     T::new(),
+            entities: Vec::new(),
+            sparse:   Vec::new(),
+        }
+    }
+
+    /// Returns the number of entities in the set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns `true` if the set contains no entities.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Returns the position of `entity`'s row in the dense arrays, or `None`
+    /// if it isn't in the set.
+    #[inline]
+    pub fn dense_index(&self, entity: u32) -> Option<usize> {
+        match self.sparse.get(entity as usize) {
+            Some(&dense) if dense != EMPTY => Some(dense as usize),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if `entity` is in the set.
+    #[inline]
+    pub fn contains(&self, entity: u32) -> bool {
+        self.dense_index(entity).is_some()
+    }
+
+    /// Gives `entity` the row `row`. If it already had one, it is replaced and
+    /// the old row is returned.
+    ///
+    /// Panics if `entity` is `u32::MAX`, which is reserved.
+    pub fn insert(&mut self, entity: u32, row: T::Row) -> Option<T::Row> {
+        assert!(entity != EMPTY, "entity id u32::MAX is reserved");
+
+        if let Some(dense) = self.dense_index(entity) {
+            self.data.push(row);
+            return Some(self.data.swap_remove(dense));
+        }
+
+        let e = entity as usize;
+        if e >= self.sparse.len() {
+            self.sparse.resize(e + 1, EMPTY);
+        }
+
+        self.sparse[e] = self.entities.len() as u32;
+        self.entities.push(entity);
+        self.data.push(row);
+        None
+    }
+
+    /// Removes `entity` from the set and returns its row, or `None` if it
+    /// wasn't in the set.
+    ///
+    /// The last row of the dense arrays is moved into the removed row's place.
+    pub fn remove(&mut self, entity: u32) -> Option<T::Row> {
+        let dense =
+            match self.dense_index(entity) {
+                None        => return None,
+                Some(dense) => dense,
+            };
+
+        let row = self.data.swap_remove(dense);
+        self.entities.swap_remove(dense);
+        self.sparse[entity as usize] = EMPTY;
+
+        if dense < self.entities.len() {
+            let moved = self.entities[dense];
+            self.sparse[moved as usize] = dense as u32;
+        }
+
+        Some(row)
+    }
+
+    /// Returns `entity`'s row, or `None` if it isn't in the set.
+    #[inline]
+    pub fn get(&self, entity: u32) -> Option<T::Ref<'_>> {
+        self.dense_index(entity).and_then(move |dense| self.data.get(dense))
+    }
+
+    /// Returns `entity`'s row mutably, or `None` if it isn't in the set.
+    #[inline]
+    pub fn get_mut(&mut self, entity: u32) -> Option<T::Mut<'_>> {
+        match self.dense_index(entity) {
+            None        => None,
+            Some(dense) => self.data.get_mut(dense),
+        }
+    }
+
+    /// Removes every entity from the set.
+    pub fn clear(&mut self) {
+        for &entity in &self.entities {
+            self.sparse[entity as usize] = EMPTY;
+        }
+        self.entities.clear();
+        self.data.clear();
+    }
+
+    /// Returns the entities in the set, in the same order as the dense arrays.
+    #[inline]
+    pub fn entities(&self) -> &[u32] {
+        &self.entities
+    }
+
+    /// Returns the table holding the rows.
+    #[inline]
+    pub fn table(&self) -> &T {
+        &self.data
+    }
+
+    /// Returns slices over the dense arrays.
+    #[inline]
+    pub fn as_slices(&self) -> T::Slices<'_> {
+        self.data.as_slices()
+    }
+
+    /// Returns mutable slices over the dense arrays.
+    ///
+    /// The rows can be changed in place, but not added or removed.
+    #[inline]
+    pub fn as_mut_slices(&mut self) -> T::MutSlices<'_> {
+        self.data.as_mut_slices()
+    }
+
+    /// Returns an iterator over every entity and its row, in dense order.
+    #[inline]
+    pub fn iter(&self) -> Iter<T> {
+        Iter { set: self, entities: self.entities.iter() }
+    }
+}
+
+impl<T: Table> Default for SparseSet<T> {
+    fn default() -> SparseSet<T> { SparseSet::new() }
+}
+
+impl<T: Table + Debug> Debug for SparseSet<T> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        f.debug_struct("SparseSet")
+         .field("entities", &self.entities)
+         .field("data", &self.data)
+         .finish()
+    }
+}
+
+/// An iterator over the entities of a `SparseSet`, and their rows.
+pub struct Iter<'a, T: 'a + Table> {
+    set:      &'a SparseSet<T>,
+    entities: slice::Iter<'a, u32>,
+}
+
+impl<'a, T: Table> Iterator for Iter<'a, T> {
+    type Item = (u32, T::Ref<'a>);
+
+    #[inline]
+    fn next(&mut self) -> Option<(u32, T::Ref<'a>)> {
+        let set = self.set;
+        self.entities.next().map(|&e| (e, set.get(e).unwrap()))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entities.size_hint()
+    }
+}
+
+/// Returns an iterator over the entities in both `a` and `b`, along with their
+/// rows from each.
+///
+/// This walks whichever set is smaller and looks each entity up in the other,
+/// so it takes time proportional to `min(a.len(), b.len())`.
+pub fn join<'a, T: Table, U: Table>(a: &'a SparseSet<T>, b: &'a SparseSet<U>) -> Join<'a, T, U> {
+    let entities =
+        if a.len() <= b.len() { a.entities.iter() } else { b.entities.iter() };
+    Join { a: a, b: b, entities: entities }
+}
+
+/// An iterator over the entities in two `SparseSet`s. See `join`.
+pub struct Join<'a, T: 'a + Table, U: 'a + Table> {
+    a:        &'a SparseSet<T>,
+    b:        &'a SparseSet<U>,
+    entities: slice::Iter<'a, u32>,
+}
+
+impl<'a, T: Table, U: Table> Iterator for Join<'a, T, U> {
+    type Item = (u32, T::Ref<'a>, U::Ref<'a>);
+
+    fn next(&mut self) -> Option<(u32, T::Ref<'a>, U::Ref<'a>)> {
+        let (a, b) = (self.a, self.b);
+        for &e in &mut self.entities {
+            if let (Some(x), Some(y)) = (a.get(e), b.get(e)) {
+                return Some((e, x, y));
+            }
+        }
+        None
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.entities.size_hint().1)
+    }
+}
@@ -0,0 +1,231 @@
+//! Reductions and summary statistics over the arrays of an SoA.
+//!
+//! The free functions work on any slice. `ColumnStats` adds methods which run
+//! them over one array of a `Soa2`, `Soa3`, or `Soa4`, picked out by index, as
+//! in `soa.sum_column::<1>()`.
+//!
+//! The reductions keep `LANES` independent accumulators, one per position in a
+//! block of `LANES` elements, and only combine them at the end. That breaks the
+//! dependency chain a naive fold has on its one accumulator, so the compiler is
+//! free to keep the accumulators in vector registers. Since every SoA array
+//! starts on a 16-byte boundary, each block of an array's elements is aligned
+//! too.
+
+use core::cmp::Ordering;
+use core::ops::Add;
+
+use column::Column;
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+
+/// The number of independent accumulators the reductions keep.
+pub const LANES: usize = 8;
+
+/// Primitive numbers which the reductions can be run over.
+pub trait Numeric: Copy + PartialOrd + Add<Output=Self> {
+    /// Returns the additive identity.
+    fn zero() -> Self;
+
+    /// Converts the number to an `f64`, rounding if need be.
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! numeric {
+    ($($t:ty)+) => {
+        $(impl Numeric for $t {
+            #[inline]
+            fn zero() -> $t { 0 as $t }
+
+            #[inline]
+            fn to_f64(self) -> f64 { self as f64 }
+        })+
+    }
+}
+
+numeric!(i8 i16 i32 i64 isize u8 u16 u32 u64 usize f32 f64);
+
+/// Returns the sum of the elements of `xs`.
+///
+/// Integer overflow is handled as it would be by `+`. Since the elements are
+/// summed in `LANES` interleaved groups, floating-point sums may round slightly
+/// differently from a left-to-right fold.
+pub fn sum<T: Numeric>(xs: &[T]) -> T {
+    let mut acc = [T::zero(); LANES];
+    let whole = xs.len() - xs.len() % LANES;
+
+    for block in xs[..whole].chunks(LANES) {
+        for j in 0..LANES {
+            acc[j] = acc[j] + block[j];
+        }
+    }
+
+    let mut total = T::zero();
+    for &a in &acc {
+        total = total + a;
+    }
+    for &x in &xs[whole..] {
+        total = total + x;
+    }
+    total
+}
+
+/// Returns the arithmetic mean of the elements of `xs`, or `None` if it is
+/// empty.
+///
+/// The elements are summed as `f64`s, so this can't overflow.
+pub fn mean<T: Numeric>(xs: &[T]) -> Option<f64> {
+    if xs.is_empty() { return None }
+
+    let mut acc = [0.0f64; LANES];
+    let whole = xs.len() - xs.len() % LANES;
+
+    for block in xs[..whole].chunks(LANES) {
+        for j in 0..LANES {
+            acc[j] += block[j].to_f64();
+        }
+    }
+
+    let mut total = 0.0;
+    for &a in &acc {
+        total += a;
+    }
+    for &x in &xs[whole..] {
+        total += x.to_f64();
+    }
+    Some(total / xs.len() as f64)
+}
+
+/// Returns the smallest and largest elements of `xs`, or `None` if it is empty.
+///
+/// Elements which are not equal to themselves, i.e. NaNs, are skipped. If every
+/// element is a NaN, this returns `None`.
+pub fn min_max<T: Numeric>(xs: &[T]) -> Option<(T, T)> {
+    let first =
+        match xs.iter().position(|x| x == x) {
+            None    => return None,
+            Some(i) => i,
+        };
+
+    let xs = &xs[first..];
+    let mut lo = [xs[0]; LANES];
+    let mut hi = [xs[0]; LANES];
+    let whole = xs.len() - xs.len() % LANES;
+
+    for block in xs[..whole].chunks(LANES) {
+        for j in 0..LANES {
+            if block[j] < lo[j] { lo[j] = block[j] }
+            if block[j] > hi[j] { hi[j] = block[j] }
+        }
+    }
+
+    let (mut min, mut max) = (xs[0], xs[0]);
+    for (&l, &h) in lo.iter().zip(hi.iter()) {
+        if l < min { min = l }
+        if h > max { max = h }
+    }
+    for &x in &xs[whole..] {
+        if x < min { min = x }
+        if x > max { max = x }
+    }
+    Some((min, max))
+}
+
+/// Returns the index of the smallest element of `xs` according to `compare`,
+/// or `None` if it is empty. Ties go to the earliest element.
+pub fn argmin_by<T, F>(xs: &[T], mut compare: F) -> Option<usize>
+  where F: FnMut(&T, &T) -> Ordering {
+    let mut best = match xs.first() { None => return None, Some(_) => 0 };
+
+    for i in 1..xs.len() {
+        if compare(&xs[i], &xs[best]) == Ordering::Less {
+            best = i;
+        }
+    }
+    Some(best)
+}
+
+/// Returns the index of the largest element of `xs` according to `compare`,
+/// or `None` if it is empty. Ties go to the earliest element.
+pub fn argmax_by<T, F>(xs: &[T], mut compare: F) -> Option<usize>
+  where F: FnMut(&T, &T) -> Ordering {
+    argmin_by(xs, |a, b| compare(b, a))
+}
+
+/// Counts the elements of `xs` falling into each of `bins` equal-width bins
+/// spanning `lo..hi`.
+///
+/// Each bin includes its lower edge, and the last bin includes `hi` too.
+/// Elements outside the range, and NaNs, are not counted.
+///
+/// Panics if `bins` is zero, or `lo` is not less than `hi`.
+pub fn histogram<T: Numeric>(xs: &[T], lo: f64, hi: f64, bins: usize) -> Vec<usize> {
+    assert!(bins != 0, "a histogram needs at least one bin");
+    assert!(lo < hi, "a histogram's range must be nonempty");
+
+    let mut counts = vec![0; bins];
+    let scale = bins as f64 / (hi - lo);
+
+    for &x in xs {
+        let x = x.to_f64();
+        if !(lo <= x && x <= hi) { continue }
+
+        let bin = ((x - lo) * scale) as usize;
+        counts[if bin < bins { bin } else { bins - 1 }] += 1;
+    }
+
+    counts
+}
+
+/// Statistics over a single array of an SoA, picked out by its index.
+pub trait ColumnStats {
+    /// Returns the sum of the `N`th array. See `stats::sum`.
+    #[inline]
+    fn sum_column<const N: usize>(&self) -> <Self as Column<N>>::Elem
+      where Self: Column<N>, <Self as Column<N>>::Elem: Numeric {
+        sum(Column::<N>::column(self))
+    }
+
+    /// Returns the mean of the `N`th array. See `stats::mean`.
+    #[inline]
+    fn mean_column<const N: usize>(&self) -> Option<f64>
+      where Self: Column<N>, <Self as Column<N>>::Elem: Numeric {
+        mean(Column::<N>::column(self))
+    }
+
+    /// Returns the smallest and largest elements of the `N`th array. See
+    /// `stats::min_max`.
+    #[inline]
+    fn min_max_column<const N: usize>(&self) -> Option<(<Self as Column<N>>::Elem, <Self as Column<N>>::Elem)>
+      where Self: Column<N>, <Self as Column<N>>::Elem: Numeric {
+        min_max(Column::<N>::column(self))
+    }
+
+    /// Returns the row holding the smallest element of the `N`th array
+    /// according to `compare`. See `stats::argmin_by`.
+    #[inline]
+    fn argmin_column_by<const N: usize, F>(&self, compare: F) -> Option<usize>
+      where Self: Column<N>, F: FnMut(&<Self as Column<N>>::Elem, &<Self as Column<N>>::Elem) -> Ordering {
+        argmin_by(Column::<N>::column(self), compare)
+    }
+
+    /// Returns the row holding the largest element of the `N`th array
+    /// according to `compare`. See `stats::argmax_by`.
+    #[inline]
+    fn argmax_column_by<const N: usize, F>(&self, compare: F) -> Option<usize>
+      where Self: Column<N>, F: FnMut(&<Self as Column<N>>::Elem, &<Self as Column<N>>::Elem) -> Ordering {
+        argmax_by(Column::<N>::column(self), compare)
+    }
+
+    /// Counts the elements of the `N`th array falling into each of `bins`
+    /// equal-width bins spanning `lo..hi`. See `stats::histogram`.
+    #[inline]
+    fn histogram_column<const N: usize>(&self, lo: f64, hi: f64, bins: usize) -> Vec<usize>
+      where Self: Column<N>, <Self as Column<N>>::Elem: Numeric {
+        histogram(Column::<N>::column(self), lo, hi, bins)
+    }
+}
+
+impl<A, B> ColumnStats for Soa2<A, B> {}
+impl<A, B, C> ColumnStats for Soa3<A, B, C> {}
+impl<A, B, C, D> ColumnStats for Soa4<A, B, C, D> {}
@@ -0,0 +1,141 @@
+//! A trait over the SoA types, for containers that keep their rows in one.
+//!
+//! `Table` exposes the handful of row operations those containers need, so
+//! that something like `SoaSlotMap<Soa3<A, B, C>>` works the same way for any
+//! number of arrays.
+
+use soa2::Soa2;
+use soa3::Soa3;
+use soa4::Soa4;
+
+/// The row operations shared by `Soa2`, `Soa3`, and `Soa4`.
+pub trait Table {
+    /// A whole row, by value: a tuple of one element per array.
+    type Row;
+
+    /// A tuple of references to the elements of one row.
+    type Ref<'a> where Self: 'a;
+
+    /// A tuple of mutable references to the elements of one row.
+    type Mut<'a> where Self: 'a;
+
+    /// A tuple of one slice per array.
+    type Slices<'a> where Self: 'a;
+
+    /// A tuple of one mutable slice per array.
+    type MutSlices<'a> where Self: 'a;
+
+    /// Constructs a new, empty table.
+    fn new() -> Self;
+
+    /// Returns the number of rows in the table.
+    fn len(&self) -> usize;
+
+    /// Appends a row to the table.
+    fn push(&mut self, row: Self::Row);
+
+    /// Removes the last row and returns it, or `None` if the table is empty.
+    fn pop(&mut self) -> Option<Self::Row>;
+
+    /// Removes the `index`th row and returns it, replacing it with the last
+    /// row.
+    fn swap_remove(&mut self, index: usize) -> Self::Row;
+
+    /// Removes the `index`th row and returns it, shifting all the rows after
+    /// it down by one.
+    fn remove(&mut self, index: usize) -> Self::Row;
+
+    /// Returns the `index`th row, or `None` if it is out of bounds.
+    fn get(&self, index: usize) -> Option<Self::Ref<'_>>;
+
+    /// Returns the `index`th row mutably, or `None` if it is out of bounds.
+    fn get_mut(&mut self, index: usize) -> Option<Self::Mut<'_>>;
+
+    /// Returns slices over the table's arrays.
+    fn as_slices(&self) -> Self::Slices<'_>;
+
+    /// Returns mutable slices over the table's arrays.
+    fn as_mut_slices(&mut self) -> Self::MutSlices<'_>;
+
+    /// Shortens the table, keeping the first `len` rows.
+    ///
+    /// If `len` is greater than the table's current length, this has no effect.
+    fn truncate(&mut self, len: usize);
+
+    /// Removes every row from the table.
+    fn clear(&mut self);
+
+    /// Keeps only the rows for which `f` returns `true`, calling it on each row
+    /// in order.
+    fn retain<F>(&mut self, f: F) where F: for<'a> FnMut(Self::Ref<'a>) -> bool;
+}
+
+/// A row type with a matching SoA, e.g. `(A, B)` is stored in a `Soa2<A, B>`.
+///
+/// This lets containers be written in terms of the rows they hold, as in
+/// `SoaHashMap<K, (A, B)>`, rather than the table that holds them.
+pub trait Row: Sized {
+    /// The SoA that stores rows of this type.
+    type Table: Table<Row = Self>;
+}
+
+macro_rules! table {
+    ($soa:ident<$($ty:ident),+>) => {
+        impl<$($ty),+> Table for $soa<$($ty),+> {
+            type Row = ($($ty),+);
+            type Ref<'a> = ($(&'a $ty),+) where Self: 'a;
+            type Mut<'a> = ($(&'a mut $ty),+) where Self: 'a;
+            type Slices<'a> = ($(&'a [$ty]),+) where Self: 'a;
+            type MutSlices<'a> = ($(&'a mut [$ty]),+) where Self: 'a;
+
+            #[inline]
+            fn new() -> Self { $soa::new() }
+
+            #[inline]
+            fn len(&self) -> usize { $soa::len(self) }
+
+            #[inline]
+            fn push(&mut self, row: ($($ty),+)) { $soa::push(self, row) }
+
+            #[inline]
+            fn pop(&mut self) -> Option<($($ty),+)> { $soa::pop(self) }
+
+            #[inline]
+            fn swap_remove(&mut self, index: usize) -> ($($ty),+) { $soa::swap_remove(self, index) }
+
+            #[inline]
+            fn remove(&mut self, index: usize) -> ($($ty),+) { $soa::remove(self, index) }
+
+            #[inline]
+            fn get(&self, index: usize) -> Option<($(&$ty),+)> { $soa::get(self, index) }
+
+            #[inline]
+            fn get_mut(&mut self, index: usize) -> Option<($(&mut $ty),+)> { $soa::get_mut(self, index) }
+
+            #[inline]
+            fn as_slices(&self) -> ($(&[$ty]),+) { $soa::as_slices(self) }
+
+            #[inline]
+            fn as_mut_slices(&mut self) -> ($(&mut [$ty]),+) { $soa::as_mut_slices(self) }
+
+            #[inline]
+            fn truncate(&mut self, len: usize) { $soa::truncate(self, len) }
+
+            #[inline]
+            fn clear(&mut self) { $soa::clear(self) }
+
+            #[inline]
+            fn retain<F>(&mut self, f: F) where F: for<'a> FnMut(($(&'a $ty),+)) -> bool {
+                $soa::retain(self, f)
+            }
+        }
+
+        impl<$($ty),+> Row for ($($ty),+) {
+            type Table = $soa<$($ty),+>;
+        }
+    }
+}
+
+table!(Soa2<A, B>);
+table!(Soa3<A, B, C>);
+table!(Soa4<A, B, C, D>);
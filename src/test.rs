@@ -237,3 +237,1025 @@ fn test_move_iter_unwrap() {
     assert_eq!(v0.len(), 0);
     assert_eq!(v1.len(), 0);
 }
+
+#[test]
+fn test_lanes_capacity() {
+    let mut v: Soa2<f32, u8> = Soa2::new();
+    v.set_lanes(8);
+    assert_eq!(v.lanes(), 8);
+
+    for i in 0..20 {
+        v.push((i as f32, i as u8));
+        assert_eq!(v.capacity() % 8, 0);
+    }
+
+    v.shrink_to_fit();
+    assert_eq!(v.capacity(), 24);
+    assert_eq!(v.padded_len(), 24);
+
+    v.reserve_exact(5);
+    assert_eq!(v.capacity() % 8, 0);
+}
+
+#[test]
+fn test_padded_slices() {
+    let mut v: Soa2<f32, i32> = Soa2::from_vecs(vec![1., 2., 3.], vec![4, 5, 6]);
+    v.set_lanes(4);
+    assert_eq!(v.capacity(), 4);
+
+    assert_eq!(v.as_padded_slices((0., -1)),
+               (&[1., 2., 3., 0.][..], &[4, 5, 6, -1][..]));
+
+    v.push((7., 8));
+    assert_eq!(v.as_padded_slices((0., -1)),
+               (&[1., 2., 3., 7.][..], &[4, 5, 6, 8][..]));
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_iter() {
+    use rayon::prelude::*;
+
+    let mut v: Soa2<u32, u64> = (0..1000u32).into_par_iter().map(|i| (i, i as u64 * 2)).collect();
+    assert_eq!(v.len(), 1000);
+    assert!(v.zip_iter().all(|(&a, &b)| b == a as u64 * 2));
+
+    v.par_iter_mut().for_each(|(a, b)| *b += *a as u64);
+    assert_eq!(v.par_iter().map(|(_, &b)| b).sum::<u64>(), 3 * 999 * 1000 / 2);
+
+    v.par_chunks_mut(64).for_each(|(a, b)| {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter_mut().zip(b.iter()) { *x = *y as u32; }
+    });
+    assert!(v.zip_iter().all(|(&a, &b)| a as u64 == b));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_columnar() {
+    use serde_json;
+
+    let v = Soa2::from_vecs(vec![1u32, 2, 3], vec![4.5f64, 5.5, 6.5]);
+    let s = serde_json::to_string(&v).unwrap();
+    assert_eq!(s, r#"{"d0":[1,2,3],"d1":[4.5,5.5,6.5]}"#);
+
+    let w: Soa2<u32, f64> = serde_json::from_str(&s).unwrap();
+    assert_eq!(v, w);
+
+    let bad: Result<Soa2<u32, f64>, _> = serde_json::from_str(r#"{"d0":[1,2],"d1":[4.5]}"#);
+    assert!(bad.is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_rows() {
+    use serde_json;
+
+    #[derive(Serialize, Deserialize)]
+    struct Table {
+        #[serde(with = "::serialize::rows")]
+        rows: Soa2<u32, bool>,
+    }
+
+    let t = Table { rows: Soa2::from_vecs(vec![1, 2], vec![true, false]) };
+    let s = serde_json::to_string(&t).unwrap();
+    assert_eq!(s, r#"{"rows":[[1,true],[2,false]]}"#);
+
+    let u: Table = serde_json::from_str(&s).unwrap();
+    assert_eq!(u.rows, t.rows);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_rows_deserialize_only() {
+    use serde_json;
+
+    /// Can be read, but not written.
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct In(u32);
+
+    #[derive(Deserialize)]
+    struct Table {
+        #[serde(deserialize_with = "::serialize::rows::deserialize")]
+        rows: Soa2<In, bool>,
+    }
+
+    let t: Table = serde_json::from_str(r#"{"rows":[[1,true],[2,false]]}"#).unwrap();
+    assert_eq!(t.rows.as_slices(), (&[In(1), In(2)][..], &[true, false][..]));
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_dump_and_map() {
+    use std::env;
+    use std::fs::{self, File};
+    use dump::{MappedSoa2, MappedSoa3};
+
+    let path = env::temp_dir().join(format!("soa-test-dump-{}", ::std::process::id()));
+
+    let v = Soa2::from_vecs(vec![1u8, 2, 3], vec![1.5f64, 2.5, 3.5]);
+    v.dump(File::create(&path).unwrap()).unwrap();
+
+    {
+        let m: MappedSoa2<u8, f64> = MappedSoa2::open(&path).unwrap();
+        assert_eq!(m.len(), 3);
+        assert_eq!(m.as_slices(), v.as_slices());
+        assert_eq!(m.as_slices().1.as_ptr() as usize % 16, 0);
+        assert_eq!(m.to_soa(), v);
+
+        assert!(MappedSoa2::<u8, f32>::open(&path).is_err());
+        assert!(MappedSoa3::<u8, f64, u8>::open(&path).is_err());
+    }
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn test_arrow_round_trip() {
+    let v = Soa2::from_vecs(vec![1i32, 2, 3], vec![0.5f64, 1.5, 2.5]);
+    let (p0, p1) = v.as_ptrs();
+
+    let batch = v.clone().into_record_batch(["id", "x"]).unwrap();
+    assert_eq!(batch.num_rows(), 3);
+    assert_eq!(batch.schema().field(1).name(), "x");
+
+    let w: Soa2<i32, f64> = Soa2::from_record_batch(batch).unwrap();
+    assert_eq!(v, w);
+
+    // Coming back always copies, into 16-byte aligned arrays.
+    assert!(w.as_ptrs().0 != p0 && w.as_ptrs().1 != p1);
+    assert_eq!(w.as_ptrs().0 as usize % 16, 0);
+    assert_eq!(w.as_ptrs().1 as usize % 16, 0);
+}
+
+#[test]
+fn test_column_access() {
+    let mut v = Soa2::from_vecs(vec![1u8, 2], vec!["a", "b"]);
+    assert_eq!(v.column::<0>(), &[1, 2][..]);
+    assert_eq!(v.column::<1>(), &["a", "b"][..]);
+
+    v.column_mut::<0>()[1] = 7;
+    assert_eq!(v.as_slices().0, &[1, 7][..]);
+}
+
+#[cfg(feature = "bytemuck")]
+#[test]
+fn test_byte_slices() {
+    use bytes::ByteSliceError;
+
+    let v = Soa2::from_vecs(vec![1u16, 2], vec![3u32, 4]);
+    assert_eq!(v.column_bytes::<0>().len(), 4);
+
+    let (b0, b1) = v.as_byte_slices();
+    assert_eq!(b1.len(), 8);
+
+    let w: Soa2<u16, u32> = Soa2::from_byte_slices(b0, b1).unwrap();
+    assert_eq!(v, w);
+
+    let bad: Result<Soa2<u16, u32>, _> = Soa2::from_byte_slices(b0, &b1[..4]);
+    assert_eq!(bad.err(), Some(ByteSliceError::UnequalLengths));
+
+    let bad: Result<Soa2<u16, u32>, _> = Soa2::from_byte_slices(&b0[..3], b1);
+    assert!(bad.is_err());
+}
+
+#[test]
+fn test_array_soa_push_pop() {
+    use ArraySoa2;
+    use array_soa2::CapacityError;
+
+    let mut v: ArraySoa2<u32, f32, 3> = ArraySoa2::new();
+    assert_eq!(v.capacity(), 3);
+    v.push((1, 1.));
+    v.push((2, 2.));
+    v.insert(0, (0, 0.));
+    assert!(v.is_full());
+    assert_eq!(v.try_push((3, 3.)), Err(CapacityError((3, 3.))));
+    assert_eq!(v.as_slices(), (&[0, 1, 2][..], &[0., 1., 2.][..]));
+    assert_eq!(v.as_ptrs().1 as usize % 16, 0);
+
+    v.retain(|(&x, _)| x != 1);
+    assert_eq!(v.as_slices(), (&[0, 2][..], &[0., 2.][..]));
+    assert_eq!(v.remove(0), (0, 0.));
+    assert_eq!(v.pop(), Some((2, 2.)));
+    assert_eq!(v.pop(), None);
+}
+
+#[test]
+fn test_array_soa_drop() {
+    use ArraySoa2;
+
+    let (mut c0, mut c1, mut c2, mut c3) = (0, 0, 0, 0);
+
+    {
+        let mut v: ArraySoa2<DropCounter, DropCounter, 4> = ArraySoa2::new();
+        v.push((DropCounter { count: &mut c0 }, DropCounter { count: &mut c1 }));
+        v.push((DropCounter { count: &mut c2 }, DropCounter { count: &mut c3 }));
+        drop(v.swap_remove(0));
+    }
+
+    assert_eq!((c0, c1, c2, c3), (1, 1, 1, 1));
+}
+
+#[test]
+fn test_small_soa_spill() {
+    use SmallSoa2;
+
+    let mut v: SmallSoa2<u32, u64, 4> = SmallSoa2::new();
+    for i in 0..4 {
+        v.push((i, i as u64 * 10));
+    }
+    assert!(!v.spilled());
+    assert_eq!(v.capacity(), 4);
+
+    v.push((4, 40));
+    assert!(v.spilled());
+    assert_eq!(v.as_slices(), (&[0, 1, 2, 3, 4][..], &[0, 10, 20, 30, 40][..]));
+    assert_eq!(v.as_ptrs().0 as usize % 16, 0);
+
+    v.retain(|(&x, _)| x % 2 == 0);
+    assert_eq!(v.len(), 3);
+    assert_eq!(v.into_soa2(), Soa2::from_vecs(vec![0, 2, 4], vec![0, 20, 40]));
+}
+
+#[test]
+fn test_small_soa_append_resize() {
+    use SmallSoa2;
+
+    let mut a: SmallSoa2<u32, u64, 4> = SmallSoa2::new();
+    let mut b: SmallSoa2<u32, u64, 4> = SmallSoa2::new();
+    a.resize(2, (1, 10));
+    b.resize(3, (2, 20));
+
+    a.append(&mut b);
+    assert!(a.spilled());
+    assert!(b.is_empty());
+    assert_eq!(a.as_slices(), (&[1, 1, 2, 2, 2][..], &[10, 10, 20, 20, 20][..]));
+
+    a.resize(1, (0, 0));
+    assert!(a > SmallSoa2::new());
+    assert_eq!(a.into_vecs(), (vec![1], vec![10]));
+}
+
+#[test]
+fn test_small_soa_append_spilled() {
+    use SmallSoa2;
+
+    let mut a: SmallSoa2<u32, String, 2> = SmallSoa2::new();
+    let mut b: SmallSoa2<u32, String, 2> = SmallSoa2::new();
+    for i in 0..3 {
+        a.push((i, i.to_string()));
+        b.push((i + 3, (i + 3).to_string()));
+    }
+    assert!(a.spilled() && b.spilled());
+
+    a.append(&mut b);
+    assert!(b.is_empty());
+    assert_eq!(a.as_slices().0, &[0, 1, 2, 3, 4, 5][..]);
+    assert_eq!(a.as_slices().1.iter().map(|s| &s[..]).collect::<Vec<_>>(),
+               vec!["0", "1", "2", "3", "4", "5"]);
+
+    // `b` is still usable after being drained
+    b.push((6, "6".to_string()));
+    assert_eq!(b.len(), 1);
+}
+
+#[test]
+fn test_small_soa_spill_drops_once() {
+    use SmallSoa2;
+
+    let (mut c0, mut c1, mut c2, mut c3) = (0, 0, 0, 0);
+
+    {
+        let mut v: SmallSoa2<DropCounter, DropCounter, 1> = SmallSoa2::new();
+        v.push((DropCounter { count: &mut c0 }, DropCounter { count: &mut c1 }));
+        v.push((DropCounter { count: &mut c2 }, DropCounter { count: &mut c3 }));
+        assert!(v.spilled());
+    }
+
+    assert_eq!((c0, c1, c2, c3), (1, 1, 1, 1));
+}
+
+#[test]
+fn test_deque_wrap_and_grow() {
+    use SoaDeque2;
+
+    let mut d: SoaDeque2<u32, u64> = SoaDeque2::with_capacity(4);
+    d.push_back((2, 20));
+    d.push_back((3, 30));
+    d.push_front((1, 10));
+    d.push_front((0, 0));
+    assert_eq!(d.capacity(), 4);
+
+    {
+        let ((h0, t0), (h1, t1)) = d.as_slices();
+        assert_eq!((h0, t0), (&[0, 1][..], &[2, 3][..]));
+        assert_eq!((h1, t1), (&[0, 10][..], &[20, 30][..]));
+    }
+
+    d.push_back((4, 40));
+    assert!(d.capacity() >= 5);
+    assert_eq!(d.zip_iter().map(|(&a, _)| a).collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+
+    assert_eq!(d.pop_front(), Some((0, 0)));
+    assert_eq!(d.pop_back(), Some((4, 40)));
+    assert_eq!(d.get(1), Some((&2, &20)));
+}
+
+#[test]
+fn test_deque_make_contiguous() {
+    use SoaDeque2;
+
+    for skew in 0..7 {
+        let mut d: SoaDeque2<u32, String> = SoaDeque2::with_capacity(7);
+        for _ in 0..skew {
+            d.push_back((99, String::new()));
+            d.pop_front();
+        }
+        for i in 0..6 {
+            d.push_back((i, i.to_string()));
+        }
+
+        let (d0, d1) = d.make_contiguous();
+        assert_eq!(d0, &[0, 1, 2, 3, 4, 5][..]);
+        assert_eq!(d1.iter().map(|s| &s[..]).collect::<Vec<_>>(), vec!["0", "1", "2", "3", "4", "5"]);
+    }
+}
+
+#[test]
+fn test_deque_from_boring_soa() {
+    use SoaDeque2;
+
+    let mut soa: Soa2<(), ()> = Soa2::new();
+    soa.push(((), ()));
+    soa.push(((), ()));
+
+    let mut d: SoaDeque2<(), ()> = SoaDeque2::from(soa);
+    assert_eq!(d.len(), 2);
+    d.push_front(((), ()));
+    d.push_back(((), ()));
+    assert_eq!(d.len(), 4);
+    assert_eq!(d.make_contiguous().0.len(), 4);
+}
+
+#[test]
+fn test_slot_map() {
+    use SoaSlotMap;
+    use Soa3;
+
+    let mut m: SoaSlotMap<Soa3<u32, f32, char>> = SoaSlotMap::new();
+    let a = m.insert((1, 1.0, 'a'));
+    let b = m.insert((2, 2.0, 'b'));
+    let c = m.insert((3, 3.0, 'c'));
+
+    assert_eq!(m.remove(a), Some((1, 1.0, 'a')));
+    assert_eq!(m.remove(a), None);
+    assert_eq!(m.get(a), None);
+
+    // `c` was moved into `a`'s place, but its key still finds it.
+    assert_eq!(m.dense_index(c), Some(0));
+    assert_eq!(m.get(c), Some((&3, &3.0, &'c')));
+    assert_eq!(m.as_slices().2, &['c', 'b'][..]);
+
+    // The freed slot is reused with a new generation.
+    let d = m.insert((4, 4.0, 'd'));
+    assert!(d != a);
+    assert_eq!(m.get(a), None);
+    assert_eq!(m.get(d), Some((&4, &4.0, &'d')));
+
+    *m.get_mut(b).unwrap().1 = 20.0;
+    assert_eq!(m.iter().map(|(k, (_, &x, _))| (k, x)).collect::<Vec<_>>(),
+               vec![(c, 3.0), (b, 20.0), (d, 4.0)]);
+}
+
+#[test]
+fn test_sparse_set_join() {
+    use sparse_set::{self, SparseSet};
+    use Soa2;
+
+    let mut pos: SparseSet<Soa2<f32, f32>> = SparseSet::new();
+    let mut vel: SparseSet<Soa2<f32, f32>> = SparseSet::new();
+
+    for e in 0..6 {
+        pos.insert(e, (e as f32, 0.0));
+    }
+    vel.insert(4, (1.0, 1.0));
+    vel.insert(1, (2.0, 2.0));
+    vel.insert(9, (3.0, 3.0));
+
+    assert_eq!(pos.insert(3, (30.0, 0.0)), Some((3.0, 0.0)));
+    assert_eq!(pos.remove(0), Some((0.0, 0.0)));
+    assert_eq!(pos.remove(0), None);
+    assert_eq!(pos.get(5), Some((&5.0, &0.0)));
+    assert_eq!(pos.entities(), &[5, 1, 2, 3, 4][..]);
+
+    let joined: Vec<_> =
+        sparse_set::join(&pos, &vel).map(|(e, (x, _), (dx, _))| (e, *x, *dx)).collect();
+    assert_eq!(joined, vec![(4, 4.0, 1.0), (1, 1.0, 2.0)]);
+
+    pos.clear();
+    assert!(!pos.contains(5));
+    assert_eq!(sparse_set::join(&pos, &vel).count(), 0);
+}
+
+#[test]
+fn test_soa_hash_map() {
+    use SoaHashMap;
+
+    let mut m: SoaHashMap<String, (u32, f64)> = SoaHashMap::new();
+    m.insert("a".to_string(), (1, 1.5));
+    m.insert("b".to_string(), (2, 2.5));
+    m.insert("c".to_string(), (3, 3.5));
+    m.insert("d".to_string(), (4, 4.5));
+
+    assert_eq!(m.insert("b".to_string(), (20, 20.5)), Some((2, 2.5)));
+    assert_eq!(m.get("b"), Some((&20, &20.5)));
+    assert_eq!(m.values_as_slices().0, &[1, 20, 3, 4][..]);
+
+    assert_eq!(m.shift_remove("a"), Some(("a".to_string(), (1, 1.5))));
+    assert_eq!(m.keys(), &["b", "c", "d"]);
+    assert_eq!(m.get_index_of("d"), Some(2));
+
+    assert_eq!(m.swap_remove("b"), Some(("b".to_string(), (20, 20.5))));
+    assert_eq!(m.swap_remove("b"), None);
+    assert_eq!(m.keys(), &["d", "c"]);
+    assert_eq!(m.get("d"), Some((&4, &4.5)));
+
+    *m.get_mut("c").unwrap().0 += 10;
+    assert_eq!(m.values_as_slices(), (&[4, 13][..], &[4.5, 3.5][..]));
+}
+
+#[test]
+fn test_soa_hash_map_collisions() {
+    use std::hash::{BuildHasherDefault, Hasher};
+    use SoaHashMap;
+
+    /// Sends every key to the same chain.
+    #[derive(Default)]
+    struct Collide;
+
+    impl Hasher for Collide {
+        fn finish(&self) -> u64 { 0 }
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    /// Keys don't have to be `Clone`.
+    #[derive(Debug, PartialEq, Eq, Hash)]
+    struct K(u32);
+
+    let mut m: SoaHashMap<K, (u32, bool), BuildHasherDefault<Collide>> = SoaHashMap::default();
+    for i in 0..6 {
+        m.insert(K(i), (i * 10, true));
+    }
+    assert_eq!(m.insert(K(3), (33, false)), Some((30, true)));
+
+    assert_eq!(m.swap_remove(&K(1)), Some((K(1), (10, true))));
+    assert_eq!(m.shift_remove(&K(0)), Some((K(0), (0, true))));
+    assert_eq!(m.keys(), &[K(5), K(2), K(3), K(4)]);
+
+    for (i, k) in m.keys().iter().enumerate() {
+        assert_eq!(m.get_index_of(k), Some(i));
+    }
+    assert_eq!(m.get(&K(3)), Some((&33, &false)));
+    assert!(!m.contains_key(&K(1)));
+}
+
+#[test]
+fn test_chunked_soa_stable_addresses() {
+    use ChunkedSoa2;
+
+    let mut c: ChunkedSoa2<u32, u64> = ChunkedSoa2::new(4);
+    for i in 0..10 {
+        assert_eq!(c.push((i, i as u64 * 10)), i as usize);
+    }
+
+    let first = c.get(0).unwrap().0 as *const u32;
+    for i in 10..100 {
+        c.push((i, i as u64 * 10));
+    }
+    assert_eq!(c.get(0).unwrap().0 as *const u32, first);
+    assert_eq!(c.num_pages(), 25);
+
+    let pages: Vec<usize> = c.pages().map(|(a, b)| { assert_eq!(a.len(), b.len()); a.len() }).collect();
+    assert_eq!(pages.len(), 25);
+    assert!(pages.iter().all(|&n| n == 4));
+
+    assert_eq!(c.swap_remove(1), (1, 10));
+    assert_eq!(c.get(1), Some((&99, &990)));
+
+    c.truncate(9);
+    assert_eq!(c.len(), 9);
+    assert_eq!(c.num_pages(), 3);
+    assert_eq!(c.pop(), Some((8, 80)));
+    assert_eq!(c.num_pages(), 2);
+    assert_eq!(c.zip_iter().map(|(&a, _)| a).collect::<Vec<_>>(), vec![0, 99, 2, 3, 4, 5, 6, 7]);
+
+    // the emptied page is kept, so crossing the boundary again doesn't allocate
+    assert_eq!(c.capacity(), 12);
+    c.push((8, 80));
+    let spare = c.get(8).unwrap().0 as *const u32;
+    c.pop();
+    c.push((8, 80));
+    assert_eq!(c.get(8).unwrap().0 as *const u32, spare);
+
+    c.pop();
+    c.shrink_to_fit();
+    assert_eq!(c.capacity(), 8);
+}
+
+#[test]
+fn test_append_soa_concurrent_push() {
+    use std::sync::Arc;
+    use std::thread;
+    use AppendSoa2;
+
+    let soa: Arc<AppendSoa2<u32, u64>> = Arc::new(AppendSoa2::new());
+
+    let threads: Vec<_> = (0..4u32).map(|t| {
+        let soa = soa.clone();
+        thread::spawn(move || {
+            for i in 0..1000 {
+                let x = t * 1000 + i;
+                soa.push((x, x as u64 * 2));
+            }
+        })
+    }).collect();
+
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    let snap = soa.snapshot();
+    assert_eq!(snap.len(), 4000);
+
+    let mut seen = vec![false; 4000];
+    let mut total = 0;
+    for (a, b) in snap.chunks() {
+        assert_eq!(a.len(), b.len());
+        assert_eq!(a.as_ptr() as usize % 16, 0);
+        for (&x, &y) in a.iter().zip(b.iter()) {
+            assert_eq!(y, x as u64 * 2);
+            assert!(!seen[x as usize]);
+            seen[x as usize] = true;
+        }
+        total += a.len();
+    }
+    assert_eq!(total, 4000);
+    assert!(snap.get(3999).is_some());
+    assert!(snap.get(4000).is_none());
+}
+
+#[test]
+fn test_cow_soa_copies_touched_columns() {
+    use CowSoa4;
+    use Soa4;
+
+    let mut s = Soa4::new();
+    for i in 0..10u32 {
+        s.push((i, i as f64, i as u8, i as u64));
+    }
+
+    let a: CowSoa4<u32, f64, u8, u64> = CowSoa4::from(s);
+    let mut b = a.clone();
+    assert_eq!(a.shared_columns(), 4);
+    assert_eq!(a.as_slices().1.as_ptr(), b.as_slices().1.as_ptr());
+
+    b.column_mut::<1>()[3] = 30.0;
+    assert_eq!(b.shared_columns(), 3);
+    assert_eq!(a.as_slices().0.as_ptr(), b.as_slices().0.as_ptr());
+    assert!(a.as_slices().1.as_ptr() != b.as_slices().1.as_ptr());
+    assert_eq!(a.get(3), Some((&3, &3.0, &3, &3)));
+    assert_eq!(b.get(3), Some((&3, &30.0, &3, &3)));
+    assert_eq!(b.as_slices().1.as_ptr() as usize % 16, 0);
+
+    b.push((10, 10.0, 10, 10));
+    assert_eq!(b.shared_columns(), 0);
+    assert_eq!(a.len(), 10);
+    assert_eq!(b.len(), 11);
+    assert_eq!(b.swap_remove(0), (0, 0.0, 0, 0));
+    assert_eq!(b.to_soa().as_slices().0[0], 10);
+}
+
+#[test]
+fn test_cow_soa_panicking_clone() {
+    use std::panic::{self, AssertUnwindSafe};
+    use CowSoa2;
+
+    /// Panics when copied.
+    #[derive(Debug, PartialEq)]
+    struct Bomb(u32);
+
+    impl Clone for Bomb {
+        fn clone(&self) -> Bomb { panic!("boom") }
+    }
+
+    let mut s = Soa2::new();
+    s.push((1u32, Bomb(1)));
+    s.push((2u32, Bomb(2)));
+
+    let a: CowSoa2<u32, Bomb> = CowSoa2::from(s);
+    let mut b = a.clone();
+
+    // The `u32` array is copied first, then copying the `Bomb`s panics.
+    let r = panic::catch_unwind(AssertUnwindSafe(|| b.push((3, Bomb(3)))));
+    assert!(r.is_err());
+    let r = panic::catch_unwind(AssertUnwindSafe(|| b.pop()));
+    assert!(r.is_err());
+    let r = panic::catch_unwind(AssertUnwindSafe(|| b.truncate(1)));
+    assert!(r.is_err());
+
+    // No array was changed before the copy failed.
+    assert_eq!(b.len(), 2);
+    assert_eq!(b.as_slices().0.len(), b.as_slices().1.len());
+    assert_eq!(b.get(1), Some((&2, &Bomb(2))));
+    assert_eq!(a.get(1), Some((&2, &Bomb(2))));
+}
+
+#[test]
+fn test_cow_soa_column_without_clone() {
+    use CowSoa2;
+
+    struct NotClone(u32);
+
+    let mut s = Soa2::new();
+    s.push((NotClone(1), 1.5f32));
+    s.push((NotClone(2), 2.5f32));
+
+    let c: CowSoa2<NotClone, f32> = CowSoa2::from(s);
+    assert_eq!(c.column::<0>().iter().map(|x| x.0).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(c.column::<1>(), &[1.5, 2.5][..]);
+}
+
+#[test]
+fn test_aos_conversions() {
+    use Soa2;
+    use Soa3;
+
+    let v: Vec<(String, u32)> = (0..5).map(|i| (i.to_string(), i)).collect();
+    let s: Soa2<String, u32> = Soa2::from(v.clone());
+    assert_eq!(s, v);
+    assert_eq!(s.to_aos(), v);
+    let back: Vec<(String, u32)> = s.into();
+    assert_eq!(back, v);
+
+    let rows: Vec<(u8, f32, u64)> = (0..200).map(|i| (i as u8, i as f32, i as u64 * 3)).collect();
+    let t = Soa3::from_aos_slice(&rows);
+    assert_eq!(t, rows);
+    assert_eq!(t.as_slices().2[150], 450);
+
+    let mut out = vec![(0, 0.0, 0); 200];
+    t.copy_to_aos(&mut out);
+    assert_eq!(out, rows);
+}
+
+#[test]
+fn test_projections() {
+    use Soa4;
+    use Soa2Slice;
+
+    fn dot(v: Soa2Slice<f32, f32>) -> f32 {
+        v.zip_iter().map(|(a, b)| a * b).fold(0.0, |acc, x| acc + x)
+    }
+
+    let mut s = Soa4::new();
+    for i in 0..4 {
+        s.push((i as f32, i as u8, 2.0f32, i as u64));
+    }
+
+    assert_eq!(dot(s.project::<0, 2>()), 12.0);
+    assert_eq!(s.project::<3, 1>().get(2), Some((&2, &2)));
+
+    {
+        let (mut xs, mut ys) = s.project_disjoint_mut::<0, 1, 2, 3>();
+        for (x, y) in xs.zip_iter_mut() { *x += 1.0; *y += 1; }
+        for (x, y) in ys.zip_iter_mut() { *x = 3.0; *y *= 10; }
+    }
+
+    assert_eq!(s.as_slices(),
+               (&[1.0, 2.0, 3.0, 4.0][..], &[1, 2, 3, 4][..],
+                &[3.0; 4][..], &[0, 10, 20, 30][..]));
+    assert_eq!(dot(s.project::<0, 2>()), 30.0);
+}
+
+#[test]
+fn test_add_and_remove_columns() {
+    use std::rc::Rc;
+    use Soa2;
+
+    let mut s = Soa2::new();
+    for i in 0..5u32 {
+        s.push((i, i as f32));
+    }
+    let p0 = s.as_ptrs().0;
+
+    let s3 = s.with_column(vec![10u64, 11, 12, 13, 14]);
+    assert_eq!(s3.as_ptrs().0, p0);
+    assert_eq!(s3.as_slices().2, &[10, 11, 12, 13, 14][..]);
+    assert_eq!(s3.as_ptrs().2 as usize % 16, 0);
+
+    let s2 = s3.drop_column::<1>();
+    assert_eq!(s2.as_ptrs().0, p0);
+    assert_eq!(s2.as_slices(), (&[0, 1, 2, 3, 4][..], &[10, 11, 12, 13, 14][..]));
+
+    let rc = Rc::new(());
+    let mut other = Soa2::with_capacity(100);
+    for i in 0..5 {
+        other.push((rc.clone(), i as u8));
+    }
+    let s4 = s2.join(other);
+    assert_eq!(s4.capacity(), 100);
+    assert_eq!(s4.get(4), Some((&4, &14, &rc, &4)));
+    assert_eq!(Rc::strong_count(&rc), 6);
+
+    let (left, right) = s4.into_parts();
+    assert_eq!(left.as_ptrs().0, p0);
+    let s3 = right.with_column(vec!['a'; 5]);
+    let s2 = s3.drop_column::<0>();
+    assert_eq!(Rc::strong_count(&rc), 1);
+    assert_eq!(s2.as_slices().1, &['a'; 5][..]);
+}
+
+#[test]
+fn test_map_column() {
+    use std::rc::Rc;
+    use Soa3;
+
+    #[derive(Debug, PartialEq)]
+    struct Meters(f32);
+
+    let rc = Rc::new(());
+    let mut s = Soa3::new();
+    for i in 0..6u32 {
+        s.push((i, i as f32, rc.clone()));
+    }
+    let (p0, p1, _) = s.as_ptrs();
+
+    // same size: the allocation is reused
+    let s = s.map_column::<1, _, _>(Meters);
+    assert_eq!(s.as_ptrs().1 as usize, p1 as usize);
+    assert_eq!(s.get(2), Some((&2, &Meters(2.0), &rc)));
+
+    // different size: a fresh aligned allocation, other arrays untouched
+    let s = s.map_column::<0, _, _>(|x| x as u64 * 1000);
+    assert_eq!(s.as_ptrs().0 as usize % 16, 0);
+    assert_eq!(s.as_slices().0, &[0, 1000, 2000, 3000, 4000, 5000][..]);
+    assert!(s.as_ptrs().0 as usize != p0 as usize);
+
+    let s = s.map_column::<2, _, _>(|r| Rc::strong_count(&r));
+    assert_eq!(Rc::strong_count(&rc), 1);
+    assert_eq!(s.len(), 6);
+}
+
+#[test]
+fn test_map_column_from_boring() {
+    use Soa2;
+
+    // pushing onto a boring SoA never reserves, so its capacity stays 0
+    let mut s: Soa2<(), ()> = Soa2::new();
+    for _ in 0..5 {
+        s.push(((), ()));
+    }
+
+    let mut i = 0;
+    let s = s.map_column::<0, _, _>(|()| { i += 1; i * 10 });
+    assert!(s.capacity() >= 5);
+    assert_eq!(s.as_slices().0, &[10, 20, 30, 40, 50][..]);
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn test_column_stats() {
+    use stats::ColumnStats;
+    use Soa2;
+
+    let mut s = Soa2::new();
+    for i in 0..21u32 {
+        s.push((i, (i as f32 - 10.0).abs()));
+    }
+    s.as_mut_slices().1[3] = ::std::f32::NAN;
+
+    assert_eq!(s.sum_column::<0>(), 210);
+    assert_eq!(s.mean_column::<0>(), Some(10.0));
+    assert_eq!(s.min_max_column::<0>(), Some((0, 20)));
+    assert_eq!(s.min_max_column::<1>(), Some((0.0, 10.0)));
+    assert_eq!(s.argmin_column_by::<1, _>(|a, b| a.partial_cmp(b).unwrap_or(::std::cmp::Ordering::Greater)), Some(10));
+    assert_eq!(s.argmax_column_by::<0, _>(|a, b| a.cmp(b)), Some(20));
+    assert_eq!(s.histogram_column::<0>(0.0, 20.0, 4), vec![5, 5, 5, 6]);
+}
+
+#[test]
+fn test_group_and_aggregate() {
+    use Soa3;
+
+    let mut s = Soa3::new();
+    for &(k, x) in &[(1u32, 1.0f64), (1, 2.0), (2, 3.0), (4, 4.0), (4, 5.0), (4, 6.0)] {
+        s.push((k, x, 'x'));
+    }
+
+    let groups: Vec<_> = s.group_by_key::<0>().map(|(&k, r)| (k, r)).collect();
+    assert_eq!(groups, vec![(1, 0..2), (2, 2..3), (4, 3..6)]);
+
+    s.swap_remove(0);
+    let aggs = s.aggregate_by::<0, (u32, f64, usize), _, _>(
+        |&k| (k, 0.0, 0),
+        |acc, (_, &x, _)| { acc.1 += x; acc.2 += 1; });
+
+    assert_eq!(aggs.as_slices(),
+               (&[4, 1, 2][..], &[15.0, 2.0, 3.0][..], &[3, 1, 1][..]));
+}
+
+#[test]
+fn test_group_nan_keys() {
+    use group;
+
+    let nan = ::std::f64::NAN;
+    let keys = [1.0, 1.0, nan, nan, 2.0];
+    let ranges: Vec<_> = group::groups(&keys).map(|(_, r)| r).collect();
+    assert_eq!(ranges, vec![0..2, 2..3, 3..4, 4..5]);
+}
+
+#[test]
+fn test_joins() {
+    use join::{self, Gather};
+    use Soa2;
+
+    let mut events = Soa2::new();
+    for &(id, t) in &[(3u32, 10u64), (1, 11), (3, 12), (7, 13)] {
+        events.push((id, t));
+    }
+
+    let mut meta = Soa2::new();
+    for &(name, id) in &[("one", 1u32), ("three", 3), ("four", 4)] {
+        meta.push((name, id));
+    }
+
+    let (l, r) = join::hash_join_gather::<0, 1, _, _>(&events, &meta);
+    assert_eq!(l.join(r).to_aos(),
+               vec![(3, 10, "three", 3), (1, 11, "one", 1), (3, 12, "three", 3)]);
+
+    let (l, r) = join::hash_left_join_gather::<0, 1, _, _>(&events, &meta, &("?", 0));
+    assert_eq!(r.as_slices().0, &["three", "one", "three", "?"][..]);
+    assert_eq!(l.len(), 4);
+
+    // sort whole rows, by way of a permutation, so each key keeps its payload
+    let mut perm: Vec<usize> = (0..events.len()).collect();
+    perm.sort_by_key(|&i| events.as_slices().0[i]);
+    let sorted_events = events.gather(&perm);
+
+    let mut perm: Vec<usize> = (0..meta.len()).collect();
+    perm.sort_by_key(|&i| meta.as_slices().1[i]);
+    let sorted_meta = meta.gather(&perm);
+
+    let (l, r) = join::merge_join_gather::<0, 1, _, _>(&sorted_events, &sorted_meta);
+    assert_eq!(l.as_slices(), (&[1, 3, 3][..], &[11, 10, 12][..]));
+    assert_eq!(r.as_slices(), (&["one", "three", "three"][..], &[1, 3, 3][..]));
+
+    // keyed on the first array of both sides, the key is only kept once
+    let mut names = Soa2::new();
+    for &(id, name) in &[(1u32, "one"), (3, "three"), (4, "four")] {
+        names.push((id, name));
+    }
+
+    let joined = join::hash_join(&events, &names);
+    assert_eq!(joined.as_slices(),
+               (&[3, 1, 3][..], &[10, 11, 12][..], &["three", "one", "three"][..]));
+
+    let joined = join::hash_left_join(&events, &names, &"?");
+    assert_eq!(joined.as_slices(),
+               (&[3, 1, 3, 7][..], &[10, 11, 12, 13][..], &["three", "one", "three", "?"][..]));
+
+    let joined = join::merge_join(&sorted_events, &names);
+    assert_eq!(joined.as_slices(),
+               (&[1, 3, 3][..], &[11, 10, 12][..], &["one", "three", "three"][..]));
+}
+
+#[test]
+fn test_masks() {
+    use mask::Bitmask;
+    use Soa2;
+
+    let mut s = Soa2::new();
+    for i in 0..130u32 {
+        s.push((i, i % 3 == 0));
+    }
+
+    let even = s.compute_mask::<0, _>(|&x| x % 2 == 0);
+    let thirds = s.compute_mask::<1, _>(|&b| b);
+    assert_eq!(even.count_ones(), 65);
+    assert_eq!((!&even).count_ones(), 65);
+
+    let sixths = &even & &thirds;
+    assert_eq!(sixths.iter_ones().collect::<Vec<_>>(),
+               (0..130).filter(|i| i % 6 == 0).collect::<Vec<_>>());
+
+    let picked: Vec<u32> = s.select(&sixths).map(|(_, (&x, _))| x).collect();
+    assert_eq!(picked.len(), 22);
+    assert_eq!(picked[21], 126);
+
+    let f = s.filter_mask(&sixths);
+    assert_eq!(f.as_slices().0, &picked[..]);
+
+    let bools: Vec<bool> = (0..130).map(|i| i == 129).collect();
+    assert_eq!(s.filter_mask(&bools[..]).as_slices().0, &[129][..]);
+    assert_eq!(Bitmask::from_bools(&bools).iter_ones().collect::<Vec<_>>(), vec![129]);
+}
+
+#[test]
+fn test_nullable_column() {
+    use std::mem;
+    use nullable::{Nullable, NullableSoa};
+    use Soa3;
+
+    assert_eq!(mem::size_of::<Nullable<u32>>(), 4);
+
+    let mut s: NullableSoa<Soa3<f32, Nullable<u32>, f64>, 1> = NullableSoa::new();
+    for i in 0..70u32 {
+        if i % 10 == 0 {
+            s.push_null((i as f32, Nullable::null(), 0.0));
+        } else {
+            s.push((i as f32, Nullable(i), i as f64));
+        }
+    }
+
+    assert_eq!(s.null_count(), 7);
+    assert_eq!(s.get(0), Some(None));
+    assert_eq!(s.get(11), Some(Some(&11)));
+    assert_eq!(s.get(70), None);
+
+    let (_, valid) = s.swap_remove(1);
+    assert!(valid);
+    assert_eq!(s.get(1), Some(Some(&69)));
+
+    s.set_nullable(11, None);
+    s.set_nullable(20, Some(200));
+    assert_eq!(s.null_count(), 7);
+    assert_eq!(s.iter().filter(|x| x.is_none()).count(), 7);
+    assert_eq!(s.pop(), Some(((68.0, Nullable(68), 68.0), true)));
+
+    let (xs, validity) = s.nullable_column();
+    assert_eq!(xs.len(), validity.len());
+    assert_eq!(xs[20], 200);
+
+    // drop the nulls, and every row whose first element is past 50
+    s.retain(|(&x, _, _), valid| valid && x < 50.0);
+    assert_eq!(s.null_count(), 0);
+    assert_eq!(s.len(), 44);
+    assert_eq!(s.get(16), Some(Some(&200)));
+
+    s.truncate(3);
+    assert_eq!(s.len(), 3);
+    assert_eq!(s.validity().len(), 3);
+}
+
+#[test]
+fn test_bit_soa() {
+    use bits::BitSoa;
+    use mask::Bitmask;
+    use Soa2;
+
+    let mut s: BitSoa<Soa2<f32, f32>> = BitSoa::new();
+    for i in 0..100 {
+        s.push((i as f32, 0.0), i % 2 == 0);
+    }
+    assert_eq!(s.bits().count_ones(), 50);
+    assert_eq!(s.words().count(), 2);
+
+    let low = Bitmask::from_fn(s.as_slices().0, |&x| x < 10.0);
+    s.and_bits(&low);
+    assert_eq!(s.bits().iter_ones().collect::<Vec<_>>(), vec![0, 2, 4, 6, 8]);
+
+    s.not_bits();
+    assert_eq!(s.bits().count_ones(), 95);
+    assert_eq!(s.words().last().unwrap() >> 36, 0);
+
+    assert_eq!(s.swap_remove(0), ((0.0, 0.0), false));
+    assert_eq!(s.get(0), Some(((&99.0, &0.0), true)));
+
+    s.retain(|(&x, _), bit| bit && x < 20.0);
+    assert_eq!(s.as_slices().0, &[1.0, 3.0, 5.0, 7.0, 9.0, 10.0, 11.0, 12.0, 13.0,
+                                   14.0, 15.0, 16.0, 17.0, 18.0, 19.0][..]);
+    assert!((0..s.len()).all(|i| s.bit(i)));
+}
+
+#[test]
+fn test_bit_soa_retain_panic() {
+    use std::panic::{self, AssertUnwindSafe};
+    use bits::BitSoa;
+    use Soa2;
+
+    let mut s: BitSoa<Soa2<u32, u32>> = BitSoa::new();
+    for i in 0..10 {
+        s.push((i, i), i % 3 == 0);
+    }
+
+    let r = panic::catch_unwind(AssertUnwindSafe(|| {
+        s.retain(|(&x, _), _| if x == 7 { panic!() } else { x % 2 == 0 });
+    }));
+    assert!(r.is_err());
+
+    // nothing was removed, and every row still has its own bit
+    assert_eq!(s.len(), 10);
+    assert_eq!(s.bits().len(), 10);
+    assert!((0..10).all(|i| s.get(i) == Some(((&(i as u32), &(i as u32)), i % 3 == 0))));
+}
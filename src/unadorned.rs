@@ -11,8 +11,16 @@ use core::usize;
 pub struct Extent {
     pub len: usize,
     pub cap: usize,
+    /// The SIMD width, in elements, that `cap` is kept a multiple of. Always a
+    /// power of two, and `1` unless padding has been asked for.
+    pub lanes: usize,
 }
 
+/// The number of rows transposed at a time when converting between an SoA and
+/// a slice of tuples of `Copy` types. Small enough that a block of every array
+/// stays in L1.
+pub const TRANSPOSE_BLOCK: usize = 64;
+
 /// Rust has the wrong parameter order.
 /// > http://internals.rust-lang.org/t/memcpy-is-backwards/1797
 #[inline(always)]
@@ -27,6 +35,33 @@ unsafe fn memmove<T>(dst: *mut T, src: *const T, n: usize) {
   ptr::copy(src, dst, n)
 }
 
+/// Rounds `n` up to the next multiple of `lanes`, which must be a power of two.
+#[inline]
+fn round_to_lanes(n: usize, lanes: usize) -> usize {
+    n.checked_add(lanes - 1).expect("`usize` overflow") & !(lanes - 1)
+}
+
+/// The capacity to grow to when pushing onto a full SoA.
+#[inline]
+fn grown_capacity(e: &Extent) -> usize {
+    let cap = max(e.cap, 2).checked_mul(2).expect("capacity overflow");
+    round_to_lanes(cap, e.lanes)
+}
+
+/// The capacity `shrink_to_fit` leaves behind: the length, padded out to a
+/// whole number of lanes.
+#[inline]
+fn shrunk_capacity(e: &Extent) -> usize {
+    round_to_lanes(e.len, e.lanes)
+}
+
+/// The length of the SoA, rounded up to a whole number of lanes. The rows in
+/// `len..padded_len` are always within the allocation.
+#[inline]
+pub fn padded_len(e: &Extent) -> usize {
+    round_to_lanes(e.len, e.lanes)
+}
+
 fn byte_length_of<A>(capacity: usize) -> usize {
     mem::size_of::<A>().checked_mul(capacity).expect("capacity overflow")
 }
@@ -66,12 +101,111 @@ unsafe fn dealloc<A>(ptr: *mut A, cap: usize) {
     deallocate(ptr as *mut u8, cap * mem::size_of::<A>(), 16);
 }
 
+/// Moves the elements of `v` into a new 16-byte aligned column with room for
+/// `cap` elements, for adding to an SoA with that capacity.
+pub unsafe fn column_from_vec<T>(mut v: Vec<T>, cap: usize) -> *mut T {
+    assert!(v.len() <= cap);
+
+    if mem::size_of::<T>() == 0 {
+        mem::forget(v);
+        return EMPTY as *mut T;
+    }
+
+    if cap == 0 { return EMPTY as *mut T }
+
+    let dst = my_alloc::<T>(cap);
+    memcpy(*dst, v.as_ptr(), v.len());
+    v.set_len(0);
+    *dst
+}
+
+/// Drops the elements of a column taken out of an SoA, and frees it.
+pub unsafe fn drop_column<T>(ptr: *mut T, e: &Extent) {
+    if e.cap != 0 {
+        Unadorned { ptr: NonZero::new(ptr) }.drop(e);
+    }
+}
+
+/// Cleans up after a panic in the middle of `map_column`.
+struct MapGuard<T, U> {
+    src:       *mut T,
+    dst:       *mut U,
+    len:       usize,
+    done:      usize,
+    fresh_cap: Option<usize>,
+}
+
+impl<T, U> Drop for MapGuard<T, U> {
+    fn drop(&mut self) {
+        unsafe {
+            for i in 0..self.done {
+                ptr::read(self.dst.offset(i as isize));
+            }
+            // the element at `done` was moved into the closure that panicked
+            for i in self.done + 1..self.len {
+                ptr::read(self.src.offset(i as isize));
+            }
+            if let Some(cap) = self.fresh_cap {
+                if cap != 0 { dealloc(self.dst, cap) }
+            }
+        }
+    }
+}
+
+/// Maps the elements of a column through `f`, returning the new column.
+///
+/// If `U` is the same size as `T`, and needs no more than the 16-byte alignment
+/// every column gets, the elements are replaced in place and the allocation is
+/// reused. Otherwise they are moved into a new allocation with room for
+/// `new_cap` elements, and the old one is freed.
+///
+/// If `f` panics, every element of the column is dropped and any new allocation
+/// is freed. The old allocation is not, so the caller must leave it to an SoA
+/// whose length has been set to 0.
+pub unsafe fn map_column<T, U, F>(src: *mut T, e: &Extent, new_cap: usize, mut f: F) -> *mut U
+  where F: FnMut(T) -> U {
+    let reuse =
+        mem::size_of::<T>() == mem::size_of::<U>()
+     && mem::min_align_of::<U>() <= 16;
+
+    let dst =
+        if reuse {
+            src as *mut U
+        } else if new_cap == 0 {
+            EMPTY as *mut U
+        } else {
+            *my_alloc::<U>(new_cap)
+        };
+
+    let mut guard = MapGuard {
+        src:       src,
+        dst:       dst,
+        len:       e.len,
+        done:      0,
+        fresh_cap: if reuse { None } else { Some(new_cap) },
+    };
+
+    while guard.done < e.len {
+        let i = guard.done as isize;
+        ptr::write(dst.offset(i), f(ptr::read(src.offset(i))));
+        guard.done += 1;
+    }
+
+    mem::forget(guard);
+
+    if !reuse && e.cap != 0 {
+        dealloc(src, e.cap);
+    }
+
+    dst
+}
+
 #[must_use]
 pub struct NewUpdate;
 
 #[inline]
 pub fn new_update(_: &[NewUpdate]) -> Extent {
-    Extent { len: 0, cap: 0 }
+    Extent { len: 0, cap: 0, lanes: 1 }
 }
 
 #[must_use]
@@ -87,7 +221,7 @@ pub fn with_capacity_update(_: &[WithCapUpdate], is_boring: bool, new_cap: usize
             new_cap
         };
 
-    Extent { len: len, cap: cap }
+    Extent { len: len, cap: cap, lanes: 1 }
 }
 
 #[must_use]
@@ -95,7 +229,7 @@ pub struct FromRawPartsUpdate;
 
 #[inline]
 pub fn from_raw_parts_update(_: &[FromRawPartsUpdate], len: usize, cap: usize) -> Extent {
-    Extent { len: len, cap: cap }
+    Extent { len: len, cap: cap, lanes: 1 }
 }
 
 #[must_use]
@@ -103,7 +237,7 @@ pub struct FromRawBufsUpdate;
 
 #[inline]
 pub fn from_raw_bufs_update(_: &[FromRawBufsUpdate], elts: usize) -> Extent {
-    Extent { len: elts, cap: elts }
+    Extent { len: elts, cap: elts, lanes: 1 }
 }
 
 #[must_use]
@@ -114,11 +248,13 @@ pub struct ReserveCalc(usize);
 pub fn calc_reserve_space(e: &Extent, additional: usize) -> Option<ReserveCalc> {
     if e.cap - e.len >= additional { return None }
 
-    Some(ReserveCalc(
+    let cap =
         e.len
         .checked_add(additional)
         .and_then(|base_len| base_len.checked_next_power_of_two())
-        .expect("`usize` overflow")))
+        .expect("`usize` overflow");
+
+    Some(ReserveCalc(round_to_lanes(cap, e.lanes)))
 }
 
 #[must_use]
@@ -133,10 +269,12 @@ pub fn reserve_update(_: &[ReserveUpdate], calc: ReserveCalc, e: &mut Extent) {
 pub fn calc_reserve_exact_space(e: &Extent, additional: usize) -> Option<ReserveCalc> {
     if e.cap - e.len >= additional { return None }
 
-    Some(ReserveCalc(
+    let cap =
         e.len
         .checked_add(additional)
-        .expect("`usize` overflow")))
+        .expect("`usize` overflow");
+
+    Some(ReserveCalc(round_to_lanes(cap, e.lanes)))
 }
 
 #[must_use]
@@ -144,7 +282,29 @@ pub struct ShrinkToFitUpdate;
 
 #[inline]
 pub fn shrink_to_fit_update(_: &[ShrinkToFitUpdate], e: &mut Extent) {
-    e.cap = e.len;
+    e.cap = shrunk_capacity(e);
+}
+
+#[must_use]
+pub struct SetLanesUpdate;
+
+#[inline]
+pub fn calc_set_lanes_space(e: &Extent, is_boring: bool, lanes: usize) -> Option<ReserveCalc> {
+    assert!(lanes.is_power_of_two(), "lane width must be a power of two");
+
+    // Zero-sized types have all the capacity they could ever want.
+    if is_boring { return None }
+
+    let cap = round_to_lanes(e.cap, lanes);
+    if cap == e.cap { return None }
+
+    Some(ReserveCalc(cap))
+}
+
+#[inline]
+pub fn set_lanes_update(_: &[SetLanesUpdate], lanes: usize, space: Option<ReserveCalc>, e: &mut Extent) {
+    e.lanes = lanes;
+    space.map(|calc| { e.cap = calc.0 });
 }
 
 #[must_use]
@@ -178,7 +338,7 @@ pub struct PushUpdate;
 #[inline]
 pub fn push_update(_: &[PushUpdate], e: &mut Extent) {
     if e.len == e.cap {
-        e.cap = max(e.cap, 2) * 2;
+        e.cap = grown_capacity(e);
     }
 
     e.len += 1;
@@ -299,7 +459,9 @@ impl<T> Unadorned<T> {
     pub unsafe fn shrink_to_fit(&mut self, e: &Extent) -> ShrinkToFitUpdate {
         if self.is_boring() { return ShrinkToFitUpdate }
 
-        if e.len == 0 {
+        let new_cap = shrunk_capacity(e);
+
+        if new_cap == 0 {
             if e.cap != 0 {
                 dealloc(*self.ptr, e.cap);
             }
@@ -307,7 +469,7 @@ impl<T> Unadorned<T> {
             let new_ptr =
                 reallocate(*self.ptr as *mut u8,
                            e.cap * mem::size_of::<T>(),
-                           e.len * mem::size_of::<T>(),
+                           new_cap * mem::size_of::<T>(),
                            mem::min_align_of::<T>()) as *mut T;
             if new_ptr.is_null() { ::alloc::oom() }
             self.ptr = NonZero::new(new_ptr);
@@ -369,8 +531,7 @@ impl<T> Unadorned<T> {
         if self.is_boring() { return }
 
         let old_size = e.cap * mem::size_of::<T>();
-        let size = max(old_size, 2 * mem::size_of::<T>()) * 2;
-        if old_size > size { panic!("capacity overflow") }
+        let size = byte_length_of::<T>(grown_capacity(e));
         self.ptr = alloc_or_realloc(*self.ptr, old_size, size);
     }
 
@@ -387,7 +548,7 @@ impl<T> Unadorned<T> {
     #[inline]
     pub unsafe fn append(&mut self, self_e: &Extent, other: &Self, other_e: &Extent, space: &Option<ReserveCalc>) -> AppendUpdate {
         space.as_ref().map(|space| self.reserve(self_e, space));
-        ptr::copy_nonoverlapping(*self.ptr, *other.ptr, other_e.len);
+        ptr::copy_nonoverlapping(*other.ptr as *const T, self.ptr.offset(self_e.len as isize), other_e.len);
 
         AppendUpdate
     }
@@ -408,6 +569,12 @@ impl<T> Unadorned<T> {
         ExtendUpdate(this_extent)
     }
 
+    #[inline]
+    pub unsafe fn set_lanes(&mut self, e: &Extent, space: &Option<ReserveCalc>) -> SetLanesUpdate {
+        space.as_ref().map(|space| self.reserve(e, space));
+        SetLanesUpdate
+    }
+
     pub unsafe fn drop(&self, e: &Extent) {
         for x in self.as_slice(e.len) {
             drop(ptr::read(x));
@@ -433,3 +600,13 @@ impl<T: Clone> Unadorned<T> {
         PushAllUpdate
     }
 }
+
+impl<T: Copy> Unadorned<T> {
+    /// Fills the rows between the length and the padded length with `x`.
+    #[inline]
+    pub unsafe fn pad(&mut self, x: T, e: &Extent) {
+        for i in e.len..padded_len(e) {
+            ptr::write(self.ptr.offset(i as isize), x);
+        }
+    }
+}